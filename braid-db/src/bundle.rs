@@ -0,0 +1,332 @@
+//! A single-file format for shipping object graphs between repositories.
+//!
+//! A bundle starts with a magic string, a format version and the hash scheme
+//! of its oids, followed by the refs it was created for and a list of entries
+//! terminated by an end tag:
+//!
+//! ```text
+//! magic      b"braid-bundle\0"
+//! version    u8
+//! scheme     null terminated algorithm name, u8 1 if hashed in domains
+//! refs       u32 count, then per ref: oid, null terminated name
+//! entries    per entry: tag u8, oid, u64 length, bytes
+//! end        tag 0
+//! ```
+//!
+//! Objects are stored as their canonical encoding and content as its raw
+//! bytes. Entries are written in dependency order, so every entry only
+//! references entries before it or objects the receiver already has. Version 1
+//! bundles have no scheme, their oids are plain BLAKE3.
+
+use std::io::{Read, Write};
+
+use braid_hash::{HashAlgorithm, HashScheme, Oid};
+
+use crate::{
+    bytes::rw::{Reader, Writer},
+    Error, Object, ObjectData, Result,
+};
+
+const MAGIC: &[u8] = b"braid-bundle\0";
+const VERSION: u8 = 2;
+
+/// The scheme of version 1 bundles, which predate the scheme header.
+const V1_SCHEME: HashScheme = HashScheme::new(HashAlgorithm::Blake3);
+
+/// The maximum number of characters in an algorithm name.
+const MAX_ALGORITHM_LEN: usize = 32;
+
+/// The maximum number of characters in a ref name.
+const MAX_REF_LEN: usize = 255;
+
+const TAG_END: u8 = 0;
+const TAG_OBJECT: u8 = 1;
+const TAG_CONTENT: u8 = 2;
+
+/// Provides the bytes of content objects when writing a bundle.
+pub trait ContentSource {
+    fn read_content(&mut self, oid: Oid) -> std::io::Result<Vec<u8>>;
+}
+
+/// Receives the bytes of content objects when reading a bundle.
+pub trait ContentSink {
+    fn write_content(&mut self, oid: Oid, bytes: &[u8]) -> std::io::Result<()>;
+}
+
+/// A named tip of the object graph in a bundle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ref {
+    pub name: String,
+    pub oid: Oid,
+}
+
+pub struct BundleWriter<W: Write> {
+    writer: Writer<W>,
+}
+
+impl<W: Write> BundleWriter<W> {
+    /// Starts a bundle whose oids are hashed with `scheme`.
+    pub fn new(writer: W, scheme: HashScheme, refs: &[Ref]) -> Result<Self> {
+        let mut writer = Writer(writer);
+        writer.0.write_all(MAGIC)?;
+        writer.write_le_bytes(VERSION)?;
+        writer.write_null_terminated_string(scheme.algorithm().as_str())?;
+        writer.write_le_bytes(u8::from(scheme.domains()))?;
+
+        let count: u32 = refs.len().try_into().expect("More than u32::MAX refs");
+        writer.write_le_bytes(count)?;
+        for r in refs {
+            writer.write_oid(r.oid)?;
+            writer.write_null_terminated_string(&r.name)?;
+        }
+
+        Ok(Self { writer })
+    }
+
+    /// Writes the canonical encoding of an object.
+    pub fn write_object(&mut self, oid: Oid, encoded: &[u8]) -> Result<()> {
+        self.write_entry(TAG_OBJECT, oid, encoded)
+    }
+
+    pub fn write_content(&mut self, oid: Oid, bytes: &[u8]) -> Result<()> {
+        self.write_entry(TAG_CONTENT, oid, bytes)
+    }
+
+    fn write_entry(&mut self, tag: u8, oid: Oid, bytes: &[u8]) -> Result<()> {
+        self.writer.write_le_bytes(tag)?;
+        self.writer.write_oid(oid)?;
+        self.writer.write_le_bytes(bytes.len() as u64)?;
+        self.writer.0.write_all(bytes)?;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<W> {
+        self.writer.write_le_bytes(TAG_END)?;
+        Ok(self.writer.into_inner())
+    }
+}
+
+/// A verified entry of a bundle.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug)]
+pub enum Entry {
+    Object { object: Object, data: ObjectData },
+    Content { oid: Oid, bytes: Vec<u8> },
+}
+
+impl Entry {
+    pub fn oid(&self) -> Oid {
+        match self {
+            Self::Object { object, .. } => object.oid(),
+            Self::Content { oid, .. } => *oid,
+        }
+    }
+}
+
+pub struct BundleReader<R: Read> {
+    reader: Reader<R>,
+    scheme: HashScheme,
+    refs: Vec<Ref>,
+    done: bool,
+}
+
+impl<R: Read> BundleReader<R> {
+    pub fn new(reader: R) -> Result<Self> {
+        let mut reader = Reader(reader);
+
+        let mut magic = [0; MAGIC.len()];
+        reader.0.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(Error::InvalidBundle("missing magic"));
+        }
+
+        let version: u8 = reader.read_le_bytes()?;
+        let scheme = match version {
+            1 => V1_SCHEME,
+            VERSION => {
+                let algorithm = reader.read_null_terminated_string(MAX_ALGORITHM_LEN)?;
+                let scheme = HashScheme::new(algorithm.parse()?);
+                let domains: u8 = reader.read_le_bytes()?;
+                match domains {
+                    0 => scheme,
+                    1 => scheme.with_domains(),
+                    _ => return Err(Error::InvalidBundle("invalid domains flag")),
+                }
+            }
+            _ => return Err(Error::InvalidBundle("unsupported version")),
+        };
+
+        let count: u32 = reader.read_le_bytes()?;
+        let mut refs = Vec::new();
+        for _ in 0..count {
+            let oid = reader.read_oid()?;
+            let name = reader.read_null_terminated_string(MAX_REF_LEN)?;
+            refs.push(Ref { name, oid });
+        }
+
+        Ok(Self {
+            reader,
+            scheme,
+            refs,
+            done: false,
+        })
+    }
+
+    /// The hash scheme of the oids in the bundle.
+    pub fn scheme(&self) -> HashScheme {
+        self.scheme
+    }
+
+    pub fn refs(&self) -> &[Ref] {
+        &self.refs
+    }
+
+    /// Reads the next entry and verifies that its bytes hash to its oid under
+    /// the bundle's [`scheme`](Self::scheme).
+    pub fn next_entry(&mut self) -> Result<Option<Entry>> {
+        if self.done {
+            return Ok(None);
+        }
+
+        let tag: u8 = self.reader.read_le_bytes()?;
+        if tag == TAG_END {
+            self.done = true;
+            return Ok(None);
+        }
+
+        let expected = self.reader.read_oid()?;
+        let len: u64 = self.reader.read_le_bytes()?;
+
+        let mut bytes = Vec::new();
+        (&mut self.reader.0).take(len).read_to_end(&mut bytes)?;
+        if bytes.len() as u64 != len {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+
+        let entry = match tag {
+            TAG_OBJECT => {
+                let (object, data) = Object::decode(&bytes, self.scheme)?;
+                Entry::Object { object, data }
+            }
+            TAG_CONTENT => Entry::Content {
+                oid: crate::content_oid(self.scheme, &bytes),
+                bytes,
+            },
+            _ => return Err(Error::InvalidBundle("unknown entry tag")),
+        };
+
+        let actual = entry.oid();
+        if actual != expected {
+            return Err(Error::HashMismatch { expected, actual });
+        }
+        Ok(Some(entry))
+    }
+}
+
+impl<R: Read> Iterator for BundleReader<R> {
+    type Item = Result<Entry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_entry().transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use braid_hash::{HashAlgorithm, HashScheme};
+
+    use super::{BundleReader, BundleWriter, Entry, Ref, MAGIC, TAG_CONTENT, TAG_END};
+    use crate::{commit::CommitData, Error, Hash, ObjectData};
+
+    fn bundle() -> Vec<u8> {
+        let scheme = HashScheme::default();
+        let (oid, encoded) = CommitData::ROOT.hash_with(scheme).unwrap();
+        let refs = [Ref {
+            name: "main".to_string(),
+            oid,
+        }];
+
+        let mut writer = BundleWriter::new(Vec::new(), scheme, &refs).unwrap();
+        writer
+            .write_content(crate::content_oid(scheme, b"hello"), b"hello")
+            .unwrap();
+        writer.write_object(oid, &encoded).unwrap();
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let bytes = bundle();
+        let mut reader = BundleReader::new(bytes.as_slice()).unwrap();
+
+        assert_eq!(reader.scheme(), HashScheme::default());
+        assert_eq!(reader.refs().len(), 1);
+        assert_eq!(reader.refs()[0].name, "main");
+
+        let entries: Vec<Entry> = reader.by_ref().collect::<Result<_, _>>().unwrap();
+        assert_eq!(entries.len(), 2);
+
+        let hello = crate::content_oid(HashScheme::default(), b"hello");
+        assert!(
+            matches!(&entries[0], Entry::Content { oid, bytes } if *oid == hello && bytes == b"hello")
+        );
+        assert!(matches!(
+            &entries[1],
+            Entry::Object {
+                data: ObjectData::Commit(_),
+                ..
+            }
+        ));
+        assert_eq!(entries[1].oid(), reader.refs()[0].oid);
+    }
+
+    #[test]
+    fn test_schemes() {
+        let scheme = HashScheme::new(HashAlgorithm::Sha256);
+        let (oid, encoded) = CommitData::root(scheme).unwrap().hash_with(scheme).unwrap();
+        let mut writer = BundleWriter::new(Vec::new(), scheme, &[]).unwrap();
+        writer.write_object(oid, &encoded).unwrap();
+        let bytes = writer.finish().unwrap();
+
+        let mut reader = BundleReader::new(bytes.as_slice()).unwrap();
+        assert_eq!(reader.scheme(), scheme);
+        assert_eq!(reader.next_entry().unwrap().unwrap().oid(), oid);
+
+        // version 1 has no scheme header and plain BLAKE3 oids
+        let hello = braid_hash::hash(b"hello");
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(1);
+        bytes.extend(0u32.to_le_bytes());
+        bytes.push(TAG_CONTENT);
+        bytes.extend(hello.as_bytes());
+        bytes.extend(5u64.to_le_bytes());
+        bytes.extend(b"hello");
+        bytes.push(TAG_END);
+
+        let mut reader = BundleReader::new(bytes.as_slice()).unwrap();
+        assert_eq!(reader.scheme(), HashScheme::new(HashAlgorithm::Blake3));
+        assert_eq!(reader.next_entry().unwrap().unwrap().oid(), hello);
+        assert!(reader.next_entry().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_corrupted() {
+        let mut bytes = bundle();
+        let hello = bytes.windows(5).position(|w| w == b"hello").unwrap();
+        bytes[hello] = b'j';
+
+        let mut reader = BundleReader::new(bytes.as_slice()).unwrap();
+        assert!(matches!(
+            reader.next_entry(),
+            Err(Error::HashMismatch { .. })
+        ));
+
+        let err = BundleReader::new(&b"not a bundle"[..]).err().unwrap();
+        assert!(matches!(err, Error::Io(_)));
+
+        let mut bytes = bundle();
+        bytes[0] = b'x';
+        let err = BundleReader::new(bytes.as_slice()).err().unwrap();
+        assert!(matches!(err, Error::InvalidBundle(_)));
+    }
+}
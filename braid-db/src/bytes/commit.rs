@@ -1,22 +1,45 @@
 use braid_hash::Oid;
 
-use crate::{commit::CommitData, ObjectKind};
+use crate::{
+    commit::{CommitData, Identity},
+    err::Error,
+    ObjectKind,
+};
 
-use super::Result;
+use super::{DecodeLimits, Result};
 
 const DATA_SIZE: usize = super::DATA_SIZE;
 
+/// Version 1 adds the committer's email and a separate author with their own
+/// date, version 2 adds extension headers. Commits are written with the oldest
+/// version that holds them, so commits from before a version keep their oids.
+const VERSION: super::FormatVersion = 2;
+
 pub(crate) type ReadCommitData = crate::commit::CommitData;
 
 impl<S: AsRef<str>> super::Hash for CommitData<S> {
     const KIND: ObjectKind = ObjectKind::Commit;
 
-    fn hash(&self) -> Result<(Oid, Vec<u8>)> {
-        hash(self)
+    fn encode(&self) -> Result<Vec<u8>> {
+        encode(self)
     }
 }
 
-fn hash(commit: &CommitData<impl AsRef<str>>) -> Result<(Oid, Vec<u8>)> {
+/// The oldest format version that can hold `commit`.
+fn min_version<S: AsRef<str>>(commit: &CommitData<S>) -> super::FormatVersion {
+    let fits_v0 = commit.committer.email.as_ref().is_empty()
+        && commit.author.email.as_ref().is_empty()
+        && commit.author.name.as_ref() == commit.committer.name.as_ref()
+        && commit.author_date == commit.date;
+
+    match () {
+        _ if !commit.headers.is_empty() => 2,
+        _ if !fits_v0 => 1,
+        _ => 0,
+    }
+}
+
+fn encode(commit: &CommitData<impl AsRef<str>>) -> Result<Vec<u8>> {
     const OIDS_SIZE: usize =
         1 /* register */ +
         1 /* parent */ +
@@ -24,12 +47,15 @@ fn hash(commit: &CommitData<impl AsRef<str>>) -> Result<(Oid, Vec<u8>)> {
         1 /* rebase_of */ +
         1 /* saves */;
 
-    const BUF_SIZE: usize = super::HEADER_SIZE + super::rw::DATETIME_SIZE + OIDS_SIZE * Oid::LEN;
+    const BUF_SIZE: usize =
+        super::HEADER_SIZE + 2 * super::rw::DATETIME_SIZE + OIDS_SIZE * Oid::LEN;
+
+    let version = min_version(commit);
 
     let buf = Vec::with_capacity(BUF_SIZE);
     let mut buf = super::rw::Writer(buf);
 
-    buf.write_kind(ObjectKind::Commit)?;
+    buf.write_header(ObjectKind::Commit, version)?;
     buf.write_zeros::<DATA_SIZE>()?;
 
     buf.write_oid(commit.register)?;
@@ -39,10 +65,30 @@ fn hash(commit: &CommitData<impl AsRef<str>>) -> Result<(Oid, Vec<u8>)> {
     buf.write_optional_oid(commit.rebase_of)?;
 
     buf.write_timestamp(commit.date)?;
+    if version >= 1 {
+        buf.write_timestamp(commit.author_date)?;
+    }
 
-    buf.write_null_terminated_string(commit.committer.as_ref())?;
+    buf.write_null_terminated_string(commit.committer.name.as_ref())?;
+    if version >= 1 {
+        buf.write_null_terminated_string(commit.committer.email.as_ref())?;
+        buf.write_null_terminated_string(commit.author.name.as_ref())?;
+        buf.write_null_terminated_string(commit.author.email.as_ref())?;
+    }
     buf.write_null_terminated_string(commit.summary.as_ref())?;
     buf.write_null_terminated_string(commit.body.as_ref())?;
+    if version >= 2 {
+        let count: u32 = commit
+            .headers
+            .len()
+            .try_into()
+            .expect("More than u32::MAX headers");
+        buf.write_le_bytes(count)?;
+        for (key, value) in &commit.headers {
+            buf.write_null_terminated_string(key.as_ref())?;
+            buf.write_null_terminated_string(value.as_ref())?;
+        }
+    }
 
     let mut buf = buf.into_inner();
     let size: u32 = buf.len().try_into().expect("More than u32::MAX bytes");
@@ -50,15 +96,42 @@ fn hash(commit: &CommitData<impl AsRef<str>>) -> Result<(Oid, Vec<u8>)> {
 
     buf[1..=DATA_SIZE].copy_from_slice(&size.to_le_bytes());
 
-    let oid = braid_hash::hash(&buf);
-    Ok((oid, buf))
+    Ok(buf)
+}
+
+/// Rejects commits encoded with a newer version than they need.
+fn check_canonical<S: AsRef<str>>(
+    commit: &CommitData<S>,
+    version: super::FormatVersion,
+) -> Result<()> {
+    if version != min_version(commit) {
+        return Err(Error::NonCanonical(
+            "commit uses a newer format version than needed",
+        ));
+    }
+    Ok(())
+}
+
+fn check_header_count(count: u32, limits: &DecodeLimits) -> Result<usize> {
+    let count = count as usize;
+    if count > limits.max_entries {
+        return Err(Error::TooManyEntries {
+            count,
+            limit: limits.max_entries,
+        });
+    }
+    Ok(count)
 }
 
-pub(crate) fn read(reader: &mut impl std::io::Read) -> Result<ReadCommitData> {
+pub(crate) fn read(
+    reader: &mut impl std::io::Read,
+    limits: &DecodeLimits,
+) -> Result<ReadCommitData> {
     let mut reader = super::rw::Reader(reader);
 
-    reader.expect_kind(ObjectKind::Commit)?;
-    reader.eat::<DATA_SIZE>()?;
+    let version = reader.expect_kind(ObjectKind::Commit)?;
+    super::check_version(ObjectKind::Commit, version, VERSION)?;
+    let mut reader = reader.take_data(ObjectKind::Commit)?;
 
     let register = reader.read_oid()?;
     let saves = reader.read_oid()?;
@@ -67,34 +140,207 @@ pub(crate) fn read(reader: &mut impl std::io::Read) -> Result<ReadCommitData> {
     let rebase_of = reader.read_optional_oid()?;
 
     let date = reader.read_timestamp()?;
+    let author_date = match version {
+        0 => date,
+        _ => reader.read_timestamp()?,
+    };
+
+    let name = reader.read_null_terminated_string(limits.max_name_len)?;
+    let (committer, author) = match version {
+        0 => (
+            Identity::new(name.clone(), String::new()),
+            Identity::new(name, String::new()),
+        ),
+        _ => {
+            let email = reader.read_null_terminated_string(limits.max_name_len)?;
+            let author = reader.read_null_terminated_string(limits.max_name_len)?;
+            let author_email = reader.read_null_terminated_string(limits.max_name_len)?;
+            (
+                Identity::new(name, email),
+                Identity::new(author, author_email),
+            )
+        }
+    };
 
-    let committer = reader.read_null_terminated_string()?;
-    let summary = reader.read_null_terminated_string()?;
+    let summary = reader.read_null_terminated_string(limits.max_text_len)?;
+    let body = reader.read_null_terminated_string(limits.max_text_len)?;
+
+    let mut headers = Vec::new();
+    if version >= 2 {
+        let count = check_header_count(reader.read_le_bytes()?, limits)?;
+        for _ in 0..count {
+            let key = reader.read_null_terminated_string(limits.max_name_len)?;
+            crate::commit::check_header_key(&key)?;
+            let value = reader.read_null_terminated_string(limits.max_text_len)?;
+            headers.push((key, value));
+        }
+    }
 
-    let body = reader.read_null_terminated_string()?;
+    reader.expect_end()?;
 
-    Ok(ReadCommitData {
+    let commit = ReadCommitData {
         register,
         parent,
         merge_parent,
         rebase_of,
         saves,
+        author,
+        author_date,
+        committer,
         date,
+        summary,
+        body,
+        headers,
+    };
+    check_canonical(&commit, version)?;
+    Ok(commit)
+}
+
+pub(crate) fn read_borrowed<'a>(
+    data: &'a [u8],
+    version: super::FormatVersion,
+    limits: &DecodeLimits,
+) -> Result<CommitData<&'a str>> {
+    super::check_version(ObjectKind::Commit, version, VERSION)?;
+    let mut reader = super::rw::SliceReader(data);
+
+    let register = reader.read_oid()?;
+    let saves = reader.read_oid()?;
+    let parent = reader.read_optional_oid()?;
+    let merge_parent = reader.read_optional_oid()?;
+    let rebase_of = reader.read_optional_oid()?;
+
+    let date = reader.read_timestamp()?;
+    let author_date = match version {
+        0 => date,
+        _ => reader.read_timestamp()?,
+    };
+
+    let name = reader.read_null_terminated_str(limits.max_name_len)?;
+    let (committer, author) = match version {
+        0 => (Identity::new(name, ""), Identity::new(name, "")),
+        _ => {
+            let email = reader.read_null_terminated_str(limits.max_name_len)?;
+            let author = reader.read_null_terminated_str(limits.max_name_len)?;
+            let author_email = reader.read_null_terminated_str(limits.max_name_len)?;
+            (
+                Identity::new(name, email),
+                Identity::new(author, author_email),
+            )
+        }
+    };
+
+    let summary = reader.read_null_terminated_str(limits.max_text_len)?;
+    let body = reader.read_null_terminated_str(limits.max_text_len)?;
+
+    let mut headers = Vec::new();
+    if version >= 2 {
+        let count = check_header_count(reader.read_le_bytes()?, limits)?;
+        for _ in 0..count {
+            let key = reader.read_null_terminated_str(limits.max_name_len)?;
+            crate::commit::check_header_key(key)?;
+            let value = reader.read_null_terminated_str(limits.max_text_len)?;
+            headers.push((key, value));
+        }
+    }
+
+    reader.expect_end()?;
+
+    let commit = CommitData {
+        register,
+        parent,
+        merge_parent,
+        rebase_of,
+        saves,
+        author,
+        author_date,
         committer,
+        date,
         summary,
         body,
-    })
+        headers,
+    };
+    check_canonical(&commit, version)?;
+    Ok(commit)
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{bytes::Hash, commit::Commit};
+    use braid_hash::{HashAlgorithm, HashScheme};
+
+    use crate::{
+        bytes::Hash,
+        commit::{Commit, Identity},
+        register::{Register, RegisterData, SaveRegister, SaveRegisterData},
+        Error,
+    };
 
     use super::CommitData;
 
     #[test]
     fn test_root() {
-        let (oid, _) = CommitData::ROOT.hash().unwrap();
+        let scheme = HashScheme::default();
+        let (oid, _) = CommitData::ROOT.hash_with(scheme).unwrap();
+        assert_eq!(oid, Commit::ROOT_ID);
+        let (oid, _) = RegisterData::<&str>::new().hash_with(scheme).unwrap();
+        assert_eq!(oid, Register::EMPTY_ID);
+        let (oid, _) = SaveRegisterData::<&str>::new().hash_with(scheme).unwrap();
+        assert_eq!(oid, SaveRegister::EMPTY_ID);
+
+        let root = CommitData::root(scheme).unwrap();
+        assert_eq!(root, CommitData::ROOT);
+
+        let sha256 = HashScheme::new(HashAlgorithm::Sha256);
+        let root = CommitData::root(sha256).unwrap();
+        assert_ne!(root.register(), Register::EMPTY_ID);
+        let (oid, encoded) = root.hash_with(sha256).unwrap();
+        assert_eq!(oid, HashAlgorithm::Sha256.hash(&encoded));
+        assert_ne!(oid, Commit::ROOT_ID);
+    }
+
+    #[test]
+    fn test_domains() {
+        let plain = HashScheme::new(HashAlgorithm::Blake3);
+        let (oid, encoded) = CommitData::ROOT.hash_with(plain).unwrap();
+        assert_eq!(crate::content_oid(plain, &encoded), oid);
+        assert_ne!(oid, Commit::ROOT_ID);
+
+        // a commit never has the oid of content with the same bytes
+        let scheme = HashScheme::default();
+        let (oid, encoded) = CommitData::ROOT.hash_with(scheme).unwrap();
         assert_eq!(oid, Commit::ROOT_ID);
+        assert_ne!(crate::content_oid(scheme, &encoded), oid);
+    }
+
+    #[test]
+    fn test_nul() {
+        let commit = |summary| {
+            CommitData::new(
+                Register::EMPTY_ID,
+                Commit::ROOT_ID,
+                None,
+                None,
+                SaveRegister::EMPTY_ID,
+                time::OffsetDateTime::UNIX_EPOCH,
+                Identity::new("alice", ""),
+                summary,
+                "",
+            )
+        };
+
+        // a NUL would end the string early and change what decodes
+        let scheme = HashScheme::default();
+        assert!(matches!(
+            commit("a\0b").hash_with(scheme),
+            Err(Error::NulInString(_))
+        ));
+        assert!(matches!(
+            commit("a").with_header("Ticket", "a\0b"),
+            Err(Error::NulInString(_))
+        ));
+
+        let mut data = commit("a").with_header("Ticket", "ABC-123").unwrap();
+        data.headers[0].1 = "a\0b";
+        assert!(matches!(data.hash_with(scheme), Err(Error::NulInString(_))));
     }
 }
@@ -0,0 +1,475 @@
+use braid_hash::HashScheme;
+
+use crate::{
+    err::Error,
+    register::{RegisterData, SaveRegisterData},
+    Object, ObjectData, ObjectDataRef, ObjectKind,
+};
+
+use super::{
+    commit, encoded_len, register, rw, save, DataSize, DecodeLimits, FormatVersion, Result,
+    HEADER_SIZE,
+};
+
+impl Object {
+    /// Decodes the canonical encoding of an object, the bytes its oid is the hash of.
+    ///
+    /// Decoding is the inverse of hashing: re-hashing the returned data with
+    /// `scheme` yields `bytes` again and the same oid as [`Object::oid`].
+    pub fn decode(bytes: &[u8], scheme: HashScheme) -> Result<(Self, ObjectData)> {
+        Self::decode_with_limits(bytes, scheme, &DecodeLimits::DEFAULT)
+    }
+
+    /// Like [`Object::decode`], but with custom [`DecodeLimits`].
+    pub fn decode_with_limits(
+        bytes: &[u8],
+        scheme: HashScheme,
+        limits: &DecodeLimits,
+    ) -> Result<(Self, ObjectData)> {
+        let (kind, version, size) = read_header(bytes)?;
+
+        let mut reader = bytes;
+        let data = match kind {
+            ObjectKind::Commit => ObjectData::Commit(commit::read(&mut reader, limits)?),
+            ObjectKind::Save => ObjectData::Save(save::read(&mut reader, limits)?),
+            ObjectKind::Register => {
+                ObjectData::Register(register::read_register(&mut reader, limits)?)
+            }
+            ObjectKind::SaveRegister => {
+                ObjectData::SaveRegister(register::read_save_register(&mut reader, limits)?)
+            }
+            ObjectKind::Content => return Err(Error::UnencodedKind(kind)),
+        };
+
+        if !reader.is_empty() {
+            return Err(Error::TrailingData(reader.len()));
+        }
+
+        Ok((Self::new(bytes, scheme, kind, version, size), data))
+    }
+
+    /// Like [`Object::decode`], but borrows strings and register entries from
+    /// `bytes` instead of allocating.
+    pub fn decode_borrowed(bytes: &[u8], scheme: HashScheme) -> Result<(Self, ObjectDataRef<'_>)> {
+        Self::decode_borrowed_with_limits(bytes, scheme, &DecodeLimits::DEFAULT)
+    }
+
+    /// Like [`Object::decode_borrowed`], but with custom [`DecodeLimits`].
+    pub fn decode_borrowed_with_limits<'a>(
+        bytes: &'a [u8],
+        scheme: HashScheme,
+        limits: &DecodeLimits,
+    ) -> Result<(Self, ObjectDataRef<'a>)> {
+        let (kind, version, size) = read_header(bytes)?;
+        let data = &bytes[HEADER_SIZE..];
+
+        let data = match kind {
+            ObjectKind::Commit => {
+                ObjectDataRef::Commit(commit::read_borrowed(data, version, limits)?)
+            }
+            ObjectKind::Save => ObjectDataRef::Save(save::read_borrowed(data, version, limits)?),
+            ObjectKind::Register => ObjectDataRef::Register(register::read_borrowed::<
+                RegisterData<&str>,
+            >(data, version, limits)?),
+            ObjectKind::SaveRegister => {
+                ObjectDataRef::SaveRegister(register::read_borrowed::<SaveRegisterData<&str>>(
+                    data, version, limits,
+                )?)
+            }
+            ObjectKind::Content => return Err(Error::UnencodedKind(kind)),
+        };
+
+        Ok((Self::new(bytes, scheme, kind, version, size), data))
+    }
+
+    /// Describes an encoding that was just produced by [`Hash::hash_with`](super::Hash::hash_with).
+    #[cfg(feature = "git")]
+    pub(crate) fn from_encoded(oid: braid_hash::Oid, bytes: &[u8]) -> Result<Self> {
+        let (kind, version, size) = read_header(bytes)?;
+        Ok(Object {
+            oid,
+            kind,
+            version,
+            size,
+        })
+    }
+
+    fn new(
+        bytes: &[u8],
+        scheme: HashScheme,
+        kind: ObjectKind,
+        version: FormatVersion,
+        size: DataSize,
+    ) -> Self {
+        Object {
+            oid: scheme.hash(kind.domain(), bytes),
+            kind,
+            version,
+            size,
+        }
+    }
+}
+
+/// Reads the header of an encoded object and checks that its declared size
+/// matches the length of `bytes`.
+fn read_header(bytes: &[u8]) -> Result<(ObjectKind, FormatVersion, DataSize)> {
+    let mut header = bytes;
+    let mut header = rw::Reader(&mut header);
+    let (kind, version) = header.read_header()?;
+    let size: DataSize = header.read_le_bytes()?;
+
+    let declared = encoded_len(kind, size)?;
+    if declared != bytes.len() {
+        return Err(Error::InvalidDataSize {
+            declared,
+            actual: bytes.len(),
+        });
+    }
+
+    Ok((kind, version, size))
+}
+
+#[cfg(test)]
+mod tests {
+    use braid_hash::{HashAlgorithm, HashScheme, Oid};
+
+    use crate::{
+        bytes::Hash,
+        commit::{Commit, CommitData, Identity},
+        register::{
+            EntryData, Register, RegisterData, RegisterDataRef, SaveRegister, SaveRegisterData,
+        },
+        save::SaveData,
+        DecodeLimits, Error, Key, Object, ObjectData, ObjectDataRef, ObjectKind, RegisterEntryKey,
+        SaveEntryKey,
+    };
+
+    fn round_trip(data: &impl Hash, expected: ObjectData, version: u8) {
+        let schemes = [
+            HashScheme::default(),
+            HashScheme::new(HashAlgorithm::Sha256),
+        ];
+        for scheme in schemes {
+            let (oid, bytes) = data.hash_with(scheme).unwrap();
+            let (object, decoded) = Object::decode(&bytes, scheme).unwrap();
+
+            assert_eq!(object.oid(), oid);
+            assert_eq!(object.kind(), expected.kind());
+            assert_eq!(object.version(), version);
+            assert_eq!(decoded, expected);
+
+            let (object, borrowed) = Object::decode_borrowed(&bytes, scheme).unwrap();
+            assert_eq!(object.oid(), oid);
+            assert_eq!(borrowed.kind(), expected.kind());
+            assert_eq!(to_owned(borrowed), expected);
+        }
+    }
+
+    fn to_owned(data: ObjectDataRef) -> ObjectData {
+        fn entries<R: EntryData<String>>(data: RegisterDataRef) -> R {
+            let mut register = R::new();
+            for (key, oid) in data {
+                register.insert(Key::try_from(key.to_string()).unwrap(), oid);
+            }
+            register
+        }
+
+        match data {
+            ObjectDataRef::Commit(c) => ObjectData::Commit(CommitData {
+                register: c.register,
+                parent: c.parent,
+                merge_parent: c.merge_parent,
+                rebase_of: c.rebase_of,
+                saves: c.saves,
+                author: Identity::new(c.author.name.to_string(), c.author.email.to_string()),
+                author_date: c.author_date,
+                committer: Identity::new(
+                    c.committer.name.to_string(),
+                    c.committer.email.to_string(),
+                ),
+                date: c.date,
+                summary: c.summary.to_string(),
+                body: c.body.to_string(),
+                headers: c
+                    .headers
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+                    .collect(),
+            }),
+            ObjectDataRef::Save(s) => ObjectData::Save(SaveData {
+                author: s.author.to_string(),
+                date: s.date,
+                content: s.content,
+                parent: s.parent,
+            }),
+            ObjectDataRef::Register(r) => ObjectData::Register(entries(r)),
+            ObjectDataRef::SaveRegister(r) => ObjectData::SaveRegister(entries(r)),
+        }
+    }
+
+    fn date() -> time::OffsetDateTime {
+        time::OffsetDateTime::from_unix_timestamp(1_700_000_000)
+            .unwrap()
+            .to_offset(time::UtcOffset::from_hms(2, 0, 0).unwrap())
+    }
+
+    #[test]
+    fn test_commit() {
+        let root = CommitData::ROOT;
+        let root = CommitData {
+            register: root.register,
+            parent: root.parent,
+            merge_parent: root.merge_parent,
+            rebase_of: root.rebase_of,
+            saves: root.saves,
+            author: Identity::default(),
+            author_date: root.author_date,
+            committer: Identity::default(),
+            date: root.date,
+            summary: String::new(),
+            body: String::new(),
+            headers: Vec::new(),
+        };
+        round_trip(&CommitData::ROOT, ObjectData::Commit(root), 0);
+
+        let commit = CommitData::new(
+            Register::EMPTY_ID,
+            Commit::ROOT_ID,
+            Some(Oid::repeat(1)),
+            None,
+            SaveRegister::EMPTY_ID,
+            date(),
+            Identity::new("committer".to_string(), String::new()),
+            "summary".to_string(),
+            "body\nwith lines".to_string(),
+        );
+        round_trip(&commit, ObjectData::Commit(commit.clone()), 0);
+
+        let commit = commit.with_author(
+            Identity::new("author".to_string(), "author@example.com".to_string()),
+            date() - time::Duration::hours(1),
+        );
+        round_trip(&commit, ObjectData::Commit(commit.clone()), 1);
+
+        let commit = commit
+            .with_header("Ticket".to_string(), "ABC-123".to_string())
+            .unwrap()
+            .with_header("Reviewed-by".to_string(), "Ada".to_string())
+            .unwrap()
+            .with_header("Ticket".to_string(), "ABC-124".to_string())
+            .unwrap();
+        round_trip(&commit, ObjectData::Commit(commit.clone()), 2);
+        let tickets: Vec<_> = commit.header_values("Ticket").collect();
+        assert_eq!(tickets, ["ABC-123", "ABC-124"]);
+
+        assert!(matches!(
+            CommitData::ROOT.with_header("no spaces", ""),
+            Err(Error::InvalidHeaderKey(_))
+        ));
+        let key = "k".repeat(255);
+        assert!(CommitData::ROOT.with_header(key.as_str(), "").is_ok());
+        let key = "k".repeat(256);
+        assert!(matches!(
+            CommitData::ROOT.with_header(key.as_str(), ""),
+            Err(Error::InvalidHeaderKey(_))
+        ));
+    }
+
+    #[test]
+    fn test_save() {
+        let save = SaveData::new("author".to_string(), date(), Oid::repeat(2), Oid::repeat(3));
+        round_trip(&save, ObjectData::Save(save.clone()), 0);
+    }
+
+    #[test]
+    fn test_registers() {
+        let mut register = RegisterData::new();
+        round_trip(&register, ObjectData::Register(register.clone()), 0);
+
+        register.insert(
+            RegisterEntryKey::try_from("b".to_string()).unwrap(),
+            Oid::repeat(4),
+        );
+        register.insert(
+            RegisterEntryKey::try_from("a".to_string()).unwrap(),
+            Oid::repeat(5),
+        );
+        round_trip(&register, ObjectData::Register(register.clone()), 0);
+
+        let mut saves = SaveRegisterData::new();
+        saves.insert(
+            SaveEntryKey::try_from("a/b".to_string()).unwrap(),
+            Oid::repeat(6),
+        );
+        round_trip(&saves, ObjectData::SaveRegister(saves.clone()), 0);
+    }
+
+    #[test]
+    fn test_limits() {
+        fn decode(bytes: &[u8], limits: &DecodeLimits) -> crate::Result<()> {
+            let owned =
+                Object::decode_with_limits(bytes, HashScheme::default(), limits).map(|_| ());
+            let borrowed =
+                Object::decode_borrowed_with_limits(bytes, HashScheme::default(), limits)
+                    .map(|_| ());
+            assert_eq!(format!("{owned:?}"), format!("{borrowed:?}"));
+            owned
+        }
+
+        let limits = DecodeLimits {
+            max_key_len: 2,
+            max_name_len: 3,
+            max_text_len: 4,
+            max_entries: 1,
+        };
+
+        let commit = |committer: &str, summary: &str| {
+            CommitData::new(
+                Register::EMPTY_ID,
+                Commit::ROOT_ID,
+                None,
+                None,
+                SaveRegister::EMPTY_ID,
+                date(),
+                Identity::new(committer.to_string(), String::new()),
+                summary.to_string(),
+                String::new(),
+            )
+        };
+
+        let bytes = commit("äöü", "four").encode().unwrap();
+        assert!(decode(&bytes, &limits).is_ok());
+
+        let bytes = commit("abcd", "").encode().unwrap();
+        assert!(matches!(
+            decode(&bytes, &limits),
+            Err(Error::StringTooLong { limit: 3 })
+        ));
+
+        let bytes = commit("", "fives").encode().unwrap();
+        assert!(matches!(
+            decode(&bytes, &limits),
+            Err(Error::StringTooLong { limit: 4 })
+        ));
+
+        let save = SaveData::new("long".to_string(), date(), Oid::ZERO, Oid::ZERO);
+        let bytes = save.encode().unwrap();
+        assert!(matches!(
+            decode(&bytes, &limits),
+            Err(Error::StringTooLong { limit: 3 })
+        ));
+
+        let mut register = RegisterData::new();
+        register.insert(
+            RegisterEntryKey::try_from("abc".to_string()).unwrap(),
+            Oid::ZERO,
+        );
+        let bytes = register.encode().unwrap();
+        assert!(matches!(
+            decode(&bytes, &limits),
+            Err(Error::StringTooLong { limit: 2 })
+        ));
+
+        register.insert(
+            RegisterEntryKey::try_from("a".to_string()).unwrap(),
+            Oid::ZERO,
+        );
+        let bytes = register.encode().unwrap();
+        assert!(matches!(
+            decode(&bytes, &limits),
+            Err(Error::TooManyEntries { count: 2, limit: 1 })
+        ));
+    }
+
+    #[test]
+    fn test_entry_count_exceeds_size() {
+        let mut bytes = RegisterData::<String>::new().encode().unwrap();
+        let len = super::super::HEADER_SIZE;
+        bytes[len..len + 4].copy_from_slice(&1000u32.to_le_bytes());
+
+        assert!(matches!(
+            Object::decode(&bytes, HashScheme::default()),
+            Err(Error::InvalidDataSize { .. })
+        ));
+    }
+
+    #[test]
+    fn test_unterminated_string() {
+        let mut bytes = CommitData::ROOT.encode().unwrap();
+        // replace the null terminator of the body
+        *bytes.last_mut().unwrap() = b'x';
+
+        assert!(matches!(
+            Object::decode(&bytes, HashScheme::default()),
+            Err(Error::Io(_))
+        ));
+    }
+
+    #[test]
+    fn test_invalid() {
+        let mut bytes = CommitData::ROOT.encode().unwrap();
+
+        bytes.push(0);
+        assert!(matches!(
+            Object::decode(&bytes, HashScheme::default()),
+            Err(Error::InvalidDataSize { .. })
+        ));
+
+        bytes.truncate(3);
+        assert!(matches!(
+            Object::decode(&bytes, HashScheme::default()),
+            Err(Error::Io(_))
+        ));
+
+        let mut bytes = CommitData::ROOT.encode().unwrap();
+        bytes[0] |= 3 << super::super::VERSION_SHIFT;
+        assert!(matches!(
+            Object::decode(&bytes, HashScheme::default()),
+            Err(Error::UnsupportedFormatVersion {
+                kind: ObjectKind::Commit,
+                version: 3
+            })
+        ));
+
+        // a version 1 commit whose author date is set back to the commit date
+        let commit = CommitData::ROOT.with_author(Identity::new("", ""), date());
+        let mut bytes = commit.encode().unwrap();
+        let date = super::super::HEADER_SIZE + 5 * Oid::LEN;
+        let len = super::super::rw::DATETIME_SIZE;
+        bytes.copy_within(date..date + len, date + len);
+        assert!(matches!(
+            Object::decode(&bytes, HashScheme::default()),
+            Err(Error::NonCanonical(_))
+        ));
+        assert!(matches!(
+            Object::decode_borrowed(&bytes, HashScheme::default()),
+            Err(Error::NonCanonical(_))
+        ));
+
+        let bytes = [ObjectKind::Content as u8, 0, 0, 0, 0];
+        assert!(matches!(
+            Object::decode(&bytes, HashScheme::default()),
+            Err(Error::UnencodedKind(ObjectKind::Content))
+        ));
+
+        // swap the order of two entries
+        let mut register = RegisterData::new();
+        register.insert(
+            RegisterEntryKey::try_from("a".to_string()).unwrap(),
+            Oid::ZERO,
+        );
+        register.insert(
+            RegisterEntryKey::try_from("b".to_string()).unwrap(),
+            Oid::ZERO,
+        );
+        let mut bytes = register.encode().unwrap();
+        let entries = super::super::HEADER_SIZE + 4;
+        let entry_len = Oid::LEN + 2;
+        bytes[entries + Oid::LEN] = b'b';
+        bytes[entries + entry_len + Oid::LEN] = b'a';
+        assert!(matches!(
+            Object::decode(&bytes, HashScheme::default()),
+            Err(Error::NonCanonical(_))
+        ));
+    }
+}
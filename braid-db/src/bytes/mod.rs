@@ -1,27 +1,119 @@
-use crate::ObjectKind;
+//! Canonical object encodings.
+//!
+//! Every encoded object starts with a header of one byte holding the
+//! [`ObjectKind`] in its low nibble and the [`FormatVersion`] of the encoding
+//! in its high nibble, followed by the little endian [`DataSize`].
+//!
+//! Each kind is versioned on its own. Version 0 is the original, unversioned
+//! encoding, so objects written before versioning keep their oids. Encoders
+//! write the current version of a kind unless the object fits an older one,
+//! decoders accept every version up to it.
+
+use braid_hash::HashScheme;
+
+use crate::{err::Error, Kind, ObjectKind};
 
 pub(crate) mod commit;
+mod decode;
 pub(crate) mod register;
 pub(crate) mod rw;
 pub(crate) mod save;
 
 pub(crate) type Result<T> = std::result::Result<T, crate::err::Error>;
 type DataSize = u32;
+pub(crate) type FormatVersion = u8;
+
+const VERSION_SHIFT: u32 = 4;
+const KIND_MASK: u8 = (1 << VERSION_SHIFT) - 1;
+const MAX_FORMAT_VERSION: FormatVersion = u8::MAX >> VERSION_SHIFT;
+
+const _: () = assert!(ObjectKind::MAX as u8 <= KIND_MASK);
 
 const DATA_SIZE: usize = std::mem::size_of::<DataSize>();
 const OBJECT_KIND_SIZE: usize = std::mem::size_of::<crate::ObjectKind>();
 const HEADER_SIZE: usize = OBJECT_KIND_SIZE + DATA_SIZE;
 
+const fn header_byte(kind: ObjectKind, version: FormatVersion) -> u8 {
+    assert!(version <= MAX_FORMAT_VERSION);
+    (version << VERSION_SHIFT) | kind as u8
+}
+
+fn split_header_byte(byte: u8) -> Result<(ObjectKind, FormatVersion)> {
+    let kind = <ObjectKind as Kind>::try_from_u8(byte & KIND_MASK)?;
+    Ok((kind, byte >> VERSION_SHIFT))
+}
+
+/// The total length of an encoded object whose header declares `size`.
+///
+/// Commits and registers declare their length without the `DataSize` field,
+/// saves declare the full length including the header.
+fn encoded_len(kind: ObjectKind, size: DataSize) -> Result<usize> {
+    let size = size as usize;
+    match kind {
+        ObjectKind::Commit | ObjectKind::Register | ObjectKind::SaveRegister => {
+            Ok(size + DATA_SIZE)
+        }
+        ObjectKind::Save => Ok(size),
+        ObjectKind::Content => Err(Error::UnencodedKind(kind)),
+    }
+}
+
+/// Bounds on what a decoder is willing to read from a single object.
+///
+/// The defaults match the column sizes of the Postgres backend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeLimits {
+    /// The maximum number of characters in a register entry key.
+    pub max_key_len: usize,
+    /// The maximum number of characters in a committer or author.
+    pub max_name_len: usize,
+    /// The maximum number of characters in a commit summary or body.
+    pub max_text_len: usize,
+    /// The maximum number of entries in a register.
+    pub max_entries: usize,
+}
+
+impl DecodeLimits {
+    pub const DEFAULT: Self = Self {
+        max_key_len: 255,
+        max_name_len: 255,
+        max_text_len: 1 << 20,
+        max_entries: 1 << 20,
+    };
+}
+
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Fails if `version` is newer than the `current` version of `kind`.
+fn check_version(kind: ObjectKind, version: FormatVersion, current: FormatVersion) -> Result<()> {
+    if version <= current {
+        Ok(())
+    } else {
+        Err(Error::UnsupportedFormatVersion { kind, version })
+    }
+}
+
 pub trait Hash: crate::sealed::Sealed {
     const KIND: ObjectKind;
 
-    fn hash(&self) -> Result<(braid_hash::Oid, Vec<u8>)>;
+    /// The canonical encoding of the object.
+    fn encode(&self) -> Result<Vec<u8>>;
+
+    /// Hashes the canonical encoding with `scheme`.
+    fn hash_with(&self, scheme: HashScheme) -> Result<(braid_hash::Oid, Vec<u8>)> {
+        let encoded = self.encode()?;
+        Ok((scheme.hash(Self::KIND.domain(), &encoded), encoded))
+    }
 }
 
 impl<T: Hash> Hash for &T {
     const KIND: ObjectKind = T::KIND;
 
-    fn hash(&self) -> Result<(braid_hash::Oid, Vec<u8>)> {
-        (*self).hash()
+    fn encode(&self) -> Result<Vec<u8>> {
+        (*self).encode()
     }
 }
@@ -1,32 +1,34 @@
 use crate::{
-    register::{EntryData, RegisterData, SaveRegisterData},
-    Key, ObjectKind, Result,
+    register::{EntryData, RegisterData, RegisterDataRef, SaveRegisterData},
+    Error, Key, Result,
 };
 
-use super::DATA_SIZE;
+use super::{DecodeLimits, DATA_SIZE};
+
+const VERSION: super::FormatVersion = 0;
 
 impl<S: AsRef<str> + Ord> super::Hash for RegisterData<S> {
     const KIND: crate::ObjectKind = <Self as EntryData<S>>::REGISTER_KIND.as_object_kind();
 
-    fn hash(&self) -> super::Result<(braid_hash::Oid, Vec<u8>)> {
-        hash(self)
+    fn encode(&self) -> super::Result<Vec<u8>> {
+        encode(self)
     }
 }
 
 impl<S: AsRef<str> + Ord> super::Hash for SaveRegisterData<S> {
     const KIND: crate::ObjectKind = <Self as EntryData<S>>::REGISTER_KIND.as_object_kind();
 
-    fn hash(&self) -> super::Result<(braid_hash::Oid, Vec<u8>)> {
-        hash(self)
+    fn encode(&self) -> super::Result<Vec<u8>> {
+        encode(self)
     }
 }
 
-fn hash<S: AsRef<str>, R: EntryData<S>>(data: &R) -> Result<(braid_hash::Oid, Vec<u8>)> {
+fn encode<S: AsRef<str>, R: EntryData<S>>(data: &R) -> Result<Vec<u8>> {
     let mut buf = Vec::new();
     let mut writer = super::rw::Writer(&mut buf);
 
     let kind = R::REGISTER_KIND.as_object_kind();
-    writer.write_kind(kind)?;
+    writer.write_header(kind, VERSION)?;
     writer.write_zeros::<DATA_SIZE>()?;
 
     let len: u32 = data.len().try_into().expect("More than u32::MAX entries");
@@ -41,35 +43,115 @@ fn hash<S: AsRef<str>, R: EntryData<S>>(data: &R) -> Result<(braid_hash::Oid, Ve
     let size = size - DATA_SIZE as u32;
     writer.write_data_size(size)?;
 
-    let oid = braid_hash::hash(&buf);
-    Ok((oid, buf))
+    Ok(buf)
 }
 
-fn read_register(reader: &mut impl std::io::Read) -> Result<RegisterData<String>> {
-    read(reader)
+pub(crate) fn read_register(
+    reader: &mut impl std::io::Read,
+    limits: &DecodeLimits,
+) -> Result<RegisterData<String>> {
+    read(reader, limits)
 }
 
-fn read_save_register(reader: &mut impl std::io::Read) -> Result<SaveRegisterData<String>> {
-    read(reader)
+pub(crate) fn read_save_register(
+    reader: &mut impl std::io::Read,
+    limits: &DecodeLimits,
+) -> Result<SaveRegisterData<String>> {
+    read(reader, limits)
 }
 
-// todo: untested
-fn read<R: EntryData<String>>(reader: &mut impl std::io::Read) -> Result<R> {
+/// The smallest possible encoding of an entry, an oid and an empty key.
+const MIN_ENTRY_SIZE: u64 = braid_hash::Oid::LEN as u64 + 1;
+
+fn read<R: EntryData<String>>(reader: &mut impl std::io::Read, limits: &DecodeLimits) -> Result<R> {
     let mut reader = super::rw::Reader(reader);
 
     let kind = R::REGISTER_KIND.as_object_kind();
-    reader.expect_kind(kind)?;
-    reader.eat::<DATA_SIZE>()?;
+    let version = reader.expect_kind(kind)?;
+    super::check_version(kind, version, VERSION)?;
+    let mut reader = reader.take_data(kind)?;
 
     let len: u32 = reader.read_le_bytes()?;
 
+    if len as usize > limits.max_entries {
+        return Err(Error::TooManyEntries {
+            count: len as usize,
+            limit: limits.max_entries,
+        });
+    }
+
+    // don't loop over entries that can't possibly fit in the declared size
+    if u64::from(len) * MIN_ENTRY_SIZE > reader.remaining() {
+        return Err(Error::InvalidDataSize {
+            declared: reader.remaining() as usize,
+            actual: len as usize * MIN_ENTRY_SIZE as usize,
+        });
+    }
+
     let mut data = R::new();
+    let mut previous: Option<String> = None;
     for _ in 0..len {
         let oid = reader.read_oid()?;
-        let name = reader.read_null_terminated_string()?;
+        let name = reader.read_null_terminated_string(limits.max_key_len)?;
+
+        // entries are written in key order, anything else would hash differently
+        if previous.as_ref().is_some_and(|previous| *previous >= name) {
+            return Err(Error::NonCanonical(
+                "register entries are not sorted by key",
+            ));
+        }
+        previous = Some(name.clone());
+
         let key = Key::try_from(name)?;
         data.insert(key, oid);
     }
 
+    reader.expect_end()?;
+
     Ok(data)
 }
+
+/// Decodes the entries of a register without allocating. All entries are
+/// validated up front so iterating the result can't fail.
+pub(crate) fn read_borrowed<'a, R: EntryData<&'a str>>(
+    data: &'a [u8],
+    version: super::FormatVersion,
+    limits: &DecodeLimits,
+) -> Result<RegisterDataRef<'a>> {
+    let kind = R::REGISTER_KIND.as_object_kind();
+    super::check_version(kind, version, VERSION)?;
+    let mut reader = super::rw::SliceReader(data);
+
+    let len: u32 = reader.read_le_bytes()?;
+
+    if len as usize > limits.max_entries {
+        return Err(Error::TooManyEntries {
+            count: len as usize,
+            limit: limits.max_entries,
+        });
+    }
+
+    let entries = reader.0;
+    let mut previous: Option<&str> = None;
+    for _ in 0..len {
+        reader.read_oid()?;
+        let name = reader.read_null_terminated_str(limits.max_key_len)?;
+
+        // entries are written in key order, anything else would hash differently
+        if previous.is_some_and(|previous| previous >= name) {
+            return Err(Error::NonCanonical(
+                "register entries are not sorted by key",
+            ));
+        }
+        previous = Some(name);
+
+        <R::Key as Key<&str>>::try_from(name)?;
+    }
+
+    reader.expect_end()?;
+
+    Ok(RegisterDataRef {
+        len: len as usize,
+        entries,
+    })
+}
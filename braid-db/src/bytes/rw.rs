@@ -1,9 +1,9 @@
 use braid_hash::Oid;
-use std::io::{Read, Write};
+use std::io::{Read, Take, Write};
 use time::UtcOffset;
 
-use super::Result;
-use crate::{err::Error, Kind, ObjectKind};
+use super::{DataSize, FormatVersion, Result};
+use crate::{err::Error, ObjectKind};
 
 type UnixTimestamp = i128;
 type OffsetSeconds = i32;
@@ -41,19 +41,34 @@ impl OffsetDateTime {
 pub(crate) struct Reader<R>(pub(crate) R);
 
 impl<R: Read> Reader<R> {
-    pub(crate) fn expect_kind(&mut self, expected: ObjectKind) -> Result<()> {
-        let actual = self.read_kind()?;
+    /// Reads the object header byte and returns the format version.
+    pub(crate) fn expect_kind(&mut self, expected: ObjectKind) -> Result<FormatVersion> {
+        let (actual, version) = self.read_header()?;
         if actual == expected {
-            Ok(())
+            Ok(version)
         } else {
             Err(Error::UnexpectedKind { expected, actual })
         }
     }
 
     #[inline]
-    pub(crate) fn eat<const N: usize>(&mut self) -> Result<()> {
-        self.0.read_exact(&mut [0; N])?;
-        Ok(())
+    pub(crate) fn read_header(&mut self) -> Result<(ObjectKind, FormatVersion)> {
+        let byte: u8 = self.read_le_bytes()?;
+        super::split_header_byte(byte)
+    }
+
+    /// Reads the `DataSize` of an object of `kind` and limits the reader to
+    /// the rest of the object's data.
+    pub(crate) fn take_data(mut self, kind: ObjectKind) -> Result<Reader<Take<R>>> {
+        let size: DataSize = self.read_le_bytes()?;
+        let len = super::encoded_len(kind, size)?;
+        let data_len = len
+            .checked_sub(super::HEADER_SIZE)
+            .ok_or(Error::InvalidDataSize {
+                declared: len,
+                actual: super::HEADER_SIZE,
+            })?;
+        Ok(Reader(self.0.take(data_len as u64)))
     }
 
     #[inline]
@@ -81,24 +96,36 @@ impl<R: Read> Reader<R> {
         OffsetDateTime::try_from_le_bytes(bytes).map(|odt| odt.0)
     }
 
+    /// Reads a string of at most `max_chars` characters up to a null byte.
     #[inline]
-    pub(crate) fn read_null_terminated_string(&mut self) -> Result<String> {
+    pub(crate) fn read_null_terminated_string(&mut self, max_chars: usize) -> Result<String> {
+        let max_len = max_utf8_len(max_chars);
         let mut buf = Vec::new();
         loop {
             let mut byte = [0];
             self.0.read_exact(&mut byte)?;
             if byte[0] == 0 {
-                break String::from_utf8(buf).map_err(|e| e.into());
+                break into_bounded_string(buf, max_chars);
+            }
+            if buf.len() == max_len {
+                break Err(Error::StringTooLong { limit: max_chars });
             }
             buf.push(byte[0]);
         }
     }
 
+    /// Reads a string of at most `max_chars` characters up to the end of the reader.
     #[inline]
-    pub(crate) fn read_string_until_end(&mut self) -> Result<String> {
-        let mut buf = String::new();
-        self.0.read_to_string(&mut buf)?;
-        Ok(buf)
+    pub(crate) fn read_string_until_end(&mut self, max_chars: usize) -> Result<String> {
+        let max_len = max_utf8_len(max_chars);
+        let mut buf = Vec::new();
+        (&mut self.0)
+            .take(max_len as u64 + 1)
+            .read_to_end(&mut buf)?;
+        if buf.len() > max_len {
+            return Err(Error::StringTooLong { limit: max_chars });
+        }
+        into_bounded_string(buf, max_chars)
     }
 
     #[inline]
@@ -107,17 +134,132 @@ impl<R: Read> Reader<R> {
         self.0.read_exact(&mut bytes)?;
         Ok(T::from_le_bytes(bytes))
     }
+}
 
+impl<R: Read> Reader<Take<R>> {
+    /// The number of bytes left in the object's data.
     #[inline]
-    pub(crate) fn read_kind<E, K: Kind<Error = E>>(&mut self) -> Result<K>
-    where
-        Error: From<E>,
-    {
-        let mut bytes = [0; 1];
-        self.0.read_exact(&mut bytes)?;
-        let byte = bytes[0];
-        let kind = K::try_from_u8(byte)?;
-        Ok(kind)
+    pub(crate) fn remaining(&self) -> u64 {
+        self.0.limit()
+    }
+
+    /// Fails if the object's data was not read completely.
+    #[inline]
+    pub(crate) fn expect_end(&self) -> Result<()> {
+        match self.remaining() {
+            0 => Ok(()),
+            n => Err(Error::TrailingData(n as usize)),
+        }
+    }
+}
+
+#[inline]
+const fn max_utf8_len(max_chars: usize) -> usize {
+    max_chars.saturating_mul(4)
+}
+
+fn into_bounded_string(buf: Vec<u8>, max_chars: usize) -> Result<String> {
+    let string = String::from_utf8(buf)?;
+    check_char_count(&string, max_chars)?;
+    Ok(string)
+}
+
+fn into_bounded_str(bytes: &[u8], max_chars: usize) -> Result<&str> {
+    let string = std::str::from_utf8(bytes)?;
+    check_char_count(string, max_chars)?;
+    Ok(string)
+}
+
+#[inline]
+fn check_char_count(string: &str, max_chars: usize) -> Result<()> {
+    if string.chars().count() > max_chars {
+        return Err(Error::StringTooLong { limit: max_chars });
+    }
+    Ok(())
+}
+
+/// Rejects strings that would end early when written null terminated.
+pub(crate) fn check_nul(string: &str) -> Result<()> {
+    if string.contains('\0') {
+        return Err(Error::NulInString(string.to_string()));
+    }
+    Ok(())
+}
+
+/// Reads borrowed data from the body of an object, after its header.
+pub(crate) struct SliceReader<'a>(pub(crate) &'a [u8]);
+
+impl<'a> SliceReader<'a> {
+    #[inline]
+    fn read_slice(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.0.len() < len {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+        let (slice, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(slice)
+    }
+
+    #[inline]
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let slice = self.read_slice(N)?;
+        Ok(slice.try_into().expect("slice has length N"))
+    }
+
+    #[inline]
+    pub(crate) fn read_oid(&mut self) -> Result<Oid> {
+        self.read_array().map(Oid::from_bytes)
+    }
+
+    #[inline]
+    pub(crate) fn read_optional_oid(&mut self) -> Result<Option<Oid>> {
+        let oid = self.read_oid()?;
+        Ok((oid != Oid::ZERO).then_some(oid))
+    }
+
+    #[inline]
+    pub(crate) fn read_timestamp(&mut self) -> Result<time::OffsetDateTime> {
+        let bytes = self.read_array()?;
+        OffsetDateTime::try_from_le_bytes(bytes).map(|odt| odt.0)
+    }
+
+    #[inline]
+    pub(crate) fn read_le_bytes<const N: usize, T: LeBytes<N>>(&mut self) -> Result<T> {
+        self.read_array().map(T::from_le_bytes)
+    }
+
+    /// Reads a string of at most `max_chars` characters up to a null byte.
+    #[inline]
+    pub(crate) fn read_null_terminated_str(&mut self, max_chars: usize) -> Result<&'a str> {
+        let max_len = max_utf8_len(max_chars);
+        match self.0.iter().position(|&b| b == 0) {
+            Some(len) if len <= max_len => {
+                let bytes = self.read_slice(len + 1)?;
+                into_bounded_str(&bytes[..len], max_chars)
+            }
+            Some(_) => Err(Error::StringTooLong { limit: max_chars }),
+            None if self.0.len() > max_len => Err(Error::StringTooLong { limit: max_chars }),
+            None => Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into()),
+        }
+    }
+
+    /// Reads a string of at most `max_chars` characters up to the end of the slice.
+    #[inline]
+    pub(crate) fn read_str_until_end(&mut self, max_chars: usize) -> Result<&'a str> {
+        if self.0.len() > max_utf8_len(max_chars) {
+            return Err(Error::StringTooLong { limit: max_chars });
+        }
+        let bytes = self.read_slice(self.0.len())?;
+        into_bounded_str(bytes, max_chars)
+    }
+
+    /// Fails if the object's data was not read completely.
+    #[inline]
+    pub(crate) fn expect_end(&self) -> Result<()> {
+        match self.0.len() {
+            0 => Ok(()),
+            n => Err(Error::TrailingData(n)),
+        }
     }
 }
 
@@ -131,8 +273,8 @@ impl<W: Write> Writer<W> {
     }
 
     #[inline]
-    pub(crate) fn write_kind(&mut self, kind: impl Kind) -> Result<()> {
-        self.0.write_all(&[kind.as_u8()])?;
+    pub(crate) fn write_header(&mut self, kind: ObjectKind, version: FormatVersion) -> Result<()> {
+        self.0.write_all(&[super::header_byte(kind, version)])?;
         Ok(())
     }
 
@@ -162,6 +304,7 @@ impl<W: Write> Writer<W> {
 
     #[inline]
     pub(crate) fn write_null_terminated_string(&mut self, string: &str) -> Result<()> {
+        check_nul(string)?;
         self.write_string(string)?;
         self.0.write_all(&[0])?;
         Ok(())
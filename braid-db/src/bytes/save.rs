@@ -1,23 +1,24 @@
 use super::Result;
 use crate::{save::SaveData, ObjectKind};
 
-const DATA_SIZE: usize = super::DATA_SIZE;
+const VERSION: super::FormatVersion = 0;
 
 pub(crate) type ReadSaveData = crate::save::SaveData;
 
 impl<S: AsRef<str>> super::Hash for SaveData<S> {
     const KIND: ObjectKind = ObjectKind::Save;
 
-    fn hash(&self) -> super::Result<(braid_hash::Oid, Vec<u8>)> {
-        hash(self)
+    fn encode(&self) -> super::Result<Vec<u8>> {
+        encode(self)
     }
 }
 
-fn hash<S: AsRef<str>>(save: &SaveData<S>) -> Result<(braid_hash::Oid, Vec<u8>)> {
-    const BUF_SIZE: usize = super::HEADER_SIZE                      // ObjectKind + DataSize
-        + super::rw::DATETIME_SIZE                                  // timestamp
-        + braid_hash::Oid::LEN                                      // content
-        + braid_hash::Oid::LEN;                                     // parent
+fn encode<S: AsRef<str>>(save: &SaveData<S>) -> Result<Vec<u8>> {
+    const BUF_SIZE: usize =
+        super::HEADER_SIZE /* ObjectKind + DataSize */ +
+        super::rw::DATETIME_SIZE /* timestamp */ +
+        braid_hash::Oid::LEN /* content */ +
+        braid_hash::Oid::LEN /* parent */;
 
     let author = save.author.as_ref();
     let data_size = BUF_SIZE + author.len();
@@ -30,7 +31,7 @@ fn hash<S: AsRef<str>>(save: &SaveData<S>) -> Result<(braid_hash::Oid, Vec<u8>)>
 
     let mut writer = super::rw::Writer(buf);
 
-    writer.write_kind(ObjectKind::Save)?;
+    writer.write_header(ObjectKind::Save, VERSION)?;
 
     writer.write_le_bytes(data_size)?;
 
@@ -48,20 +49,23 @@ fn hash<S: AsRef<str>>(save: &SaveData<S>) -> Result<(braid_hash::Oid, Vec<u8>)>
         debug_assert_eq!(buf.len(), data_size as usize);
     }
 
-    Ok((braid_hash::hash(&buf), buf))
+    Ok(buf)
 }
 
-// todo: untested
-pub(crate) fn read(reader: &mut impl std::io::Read) -> super::Result<ReadSaveData> {
+pub(crate) fn read(
+    reader: &mut impl std::io::Read,
+    limits: &super::DecodeLimits,
+) -> super::Result<ReadSaveData> {
     let mut reader = super::rw::Reader(reader);
 
-    reader.expect_kind(ObjectKind::Save)?;
-    reader.eat::<DATA_SIZE>()?;
+    let version = reader.expect_kind(ObjectKind::Save)?;
+    super::check_version(ObjectKind::Save, version, VERSION)?;
+    let mut reader = reader.take_data(ObjectKind::Save)?;
 
     let date = reader.read_timestamp()?;
     let content = reader.read_oid()?;
     let parent = reader.read_oid()?;
-    let author = reader.read_string_until_end()?;
+    let author = reader.read_string_until_end(limits.max_name_len)?;
 
     Ok(ReadSaveData {
         date,
@@ -70,3 +74,24 @@ pub(crate) fn read(reader: &mut impl std::io::Read) -> super::Result<ReadSaveDat
         author,
     })
 }
+
+pub(crate) fn read_borrowed<'a>(
+    data: &'a [u8],
+    version: super::FormatVersion,
+    limits: &super::DecodeLimits,
+) -> super::Result<SaveData<&'a str>> {
+    super::check_version(ObjectKind::Save, version, VERSION)?;
+    let mut reader = super::rw::SliceReader(data);
+
+    let date = reader.read_timestamp()?;
+    let content = reader.read_oid()?;
+    let parent = reader.read_oid()?;
+    let author = reader.read_str_until_end(limits.max_name_len)?;
+
+    Ok(SaveData {
+        date,
+        content,
+        parent,
+        author,
+    })
+}
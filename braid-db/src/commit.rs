@@ -1,22 +1,73 @@
-use braid_hash::Oid;
+use braid_hash::{HashScheme, Oid};
 
-use crate::register::{Register, SaveRegister};
+use crate::{
+    oid::{CommitOid, RegisterOid, SaveRegisterOid, ValidOid},
+    register::{Register, RegisterData, SaveRegister, SaveRegisterData},
+    Error, Hash, Result,
+};
 
-#[derive(Clone, Debug)]
-#[cfg_attr(feature = "postgres", derive(sqlx::FromRow))]
+/// A name and email, as recorded for the author and committer of a commit.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Identity<S = String> {
+    pub(crate) name: S,
+    pub(crate) email: S,
+}
+
+impl<S> Identity<S> {
+    pub const fn new(name: S, email: S) -> Self {
+        Self { name, email }
+    }
+
+    pub fn name(&self) -> &S {
+        &self.name
+    }
+
+    pub fn email(&self) -> &S {
+        &self.email
+    }
+}
+
+impl<S: AsRef<str>> std::fmt::Display for Identity<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.email.as_ref() {
+            "" => f.write_str(self.name.as_ref()),
+            email => write!(f, "{} <{email}>", self.name.as_ref()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(deserialize = "S: serde::Deserialize<'de> + AsRef<str>"))
+)]
 pub struct CommitData<S = String> {
     pub(crate) register: Oid,
     pub(crate) parent: Option<Oid>,
     pub(crate) merge_parent: Option<Oid>,
     pub(crate) rebase_of: Option<Oid>,
     pub(crate) saves: Oid,
+    pub(crate) author: Identity<S>,
+    #[cfg_attr(feature = "serde", serde(with = "time::serde::rfc3339"))]
+    pub(crate) author_date: time::OffsetDateTime,
+    pub(crate) committer: Identity<S>,
+    #[cfg_attr(feature = "serde", serde(with = "time::serde::rfc3339"))]
     pub(crate) date: time::OffsetDateTime,
-    pub(crate) committer: S,
     pub(crate) summary: S,
     pub(crate) body: S,
+    /// Ordered key/value metadata, keys can repeat.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default = "Vec::new", deserialize_with = "deserialize_headers")
+    )]
+    pub(crate) headers: Vec<(S, S)>,
 }
 
-impl<S> CommitData<S> {
+impl<S: Clone> CommitData<S> {
+    /// Creates a commit authored by its committer, see [`CommitData::with_author`].
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         register: Oid,
         parent: Oid,
@@ -24,7 +75,7 @@ impl<S> CommitData<S> {
         rebase_of: Option<Oid>,
         saves: Oid,
         date: time::OffsetDateTime,
-        committer: S,
+        committer: Identity<S>,
         summary: S,
         body: S,
     ) -> Self {
@@ -34,13 +85,52 @@ impl<S> CommitData<S> {
             merge_parent,
             rebase_of,
             saves,
-            date,
+            author: committer.clone(),
+            author_date: date,
             committer,
+            date,
             summary,
             body,
+            headers: Vec::new(),
         }
     }
 
+    /// Like [`CommitData::new`], but only accepts oids that were validated
+    /// against a store, so a register can't end up in a `parent` slot.
+    #[allow(clippy::too_many_arguments)]
+    pub fn from_valid(
+        register: RegisterOid,
+        parent: CommitOid,
+        merge_parent: Option<CommitOid>,
+        rebase_of: Option<CommitOid>,
+        saves: SaveRegisterOid,
+        date: time::OffsetDateTime,
+        committer: Identity<S>,
+        summary: S,
+        body: S,
+    ) -> Self {
+        Self::new(
+            register.oid(),
+            parent.oid(),
+            merge_parent.map(|o| o.oid()),
+            rebase_of.map(|o| o.oid()),
+            saves.oid(),
+            date,
+            committer,
+            summary,
+            body,
+        )
+    }
+}
+
+impl<S> CommitData<S> {
+    /// Records who wrote the change and when, if that differs from the committer.
+    pub fn with_author(mut self, author: Identity<S>, date: time::OffsetDateTime) -> Self {
+        self.author = author;
+        self.author_date = date;
+        self
+    }
+
     pub fn register(&self) -> Oid {
         self.register
     }
@@ -61,14 +151,23 @@ impl<S> CommitData<S> {
         self.saves
     }
 
-    pub fn date(&self) -> time::OffsetDateTime {
-        self.date
+    pub fn author(&self) -> &Identity<S> {
+        &self.author
     }
 
-    pub fn committer(&self) -> &S {
+    pub fn author_date(&self) -> time::OffsetDateTime {
+        self.author_date
+    }
+
+    pub fn committer(&self) -> &Identity<S> {
         &self.committer
     }
 
+    /// The date the commit was made, which can differ from its [author date](Self::author_date).
+    pub fn date(&self) -> time::OffsetDateTime {
+        self.date
+    }
+
     pub fn summary(&self) -> &S {
         &self.summary
     }
@@ -76,6 +175,61 @@ impl<S> CommitData<S> {
     pub fn body(&self) -> &S {
         &self.body
     }
+
+    pub fn headers(&self) -> &[(S, S)] {
+        &self.headers
+    }
+}
+
+impl<S: AsRef<str>> CommitData<S> {
+    /// Appends an extension header, such as `Ticket: ABC-123`.
+    ///
+    /// Keys are made of 1 to 255 ASCII letters, digits and `-`, values can't
+    /// contain NUL.
+    pub fn with_header(mut self, key: S, value: S) -> Result<Self> {
+        check_header(key.as_ref(), value.as_ref())?;
+        self.headers.push((key, value));
+        Ok(self)
+    }
+
+    /// The values of every header named `key`, in order.
+    pub fn header_values<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a S> + 'a {
+        self.headers
+            .iter()
+            .filter(move |(k, _)| k.as_ref() == key)
+            .map(|(_, value)| value)
+    }
+}
+
+/// The longest header key, as stored in `braid.commit_header`.
+const MAX_HEADER_KEY_LEN: usize = 255;
+
+pub(crate) fn check_header_key(key: &str) -> Result<()> {
+    let valid = (1..=MAX_HEADER_KEY_LEN).contains(&key.len())
+        && key.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-');
+    match valid {
+        true => Ok(()),
+        false => Err(Error::InvalidHeaderKey(key.to_string())),
+    }
+}
+
+fn check_header(key: &str, value: &str) -> Result<()> {
+    check_header_key(key)?;
+    crate::bytes::rw::check_nul(value)
+}
+
+/// Deserializes headers, validating them like [`CommitData::with_header`].
+#[cfg(feature = "serde")]
+fn deserialize_headers<'de, D, S>(deserializer: D) -> std::result::Result<Vec<(S, S)>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    S: serde::Deserialize<'de> + AsRef<str>,
+{
+    let headers = <Vec<(S, S)> as serde::Deserialize>::deserialize(deserializer)?;
+    for (key, value) in &headers {
+        check_header(key.as_ref(), value.as_ref()).map_err(serde::de::Error::custom)?;
+    }
+    Ok(headers)
 }
 
 impl CommitData<&'static str> {
@@ -85,16 +239,24 @@ impl CommitData<&'static str> {
         merge_parent: None,
         rebase_of: None,
         saves: SaveRegister::EMPTY_ID,
+        author: Identity::new("", ""),
+        author_date: time::OffsetDateTime::UNIX_EPOCH,
+        committer: Identity::new("", ""),
         date: time::OffsetDateTime::UNIX_EPOCH,
-        committer: "",
         summary: "",
         body: "",
+        headers: Vec::new(),
     };
 }
 
 impl<S> crate::sealed::Sealed for CommitData<S> {}
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(deserialize = "S: serde::Deserialize<'de> + AsRef<str>"))
+)]
 pub struct Commit<S = String> {
     pub(crate) id: Oid,
     pub(crate) data: CommitData<S>,
@@ -110,13 +272,56 @@ impl<S> Commit<S> {
     }
 }
 
+impl CommitData<&'static str> {
+    /// The root commit of a repository hashing with `scheme`, which is
+    /// [`CommitData::ROOT`] for the default scheme.
+    pub fn root(scheme: HashScheme) -> Result<Self> {
+        Ok(Self {
+            register: RegisterData::<&str>::new().hash_with(scheme)?.0,
+            saves: SaveRegisterData::<&str>::new().hash_with(scheme)?.0,
+            ..Self::ROOT
+        })
+    }
+}
+
 impl Commit<&'static str> {
+    /// The id of [`Commit::ROOT`], hashed with the default scheme.
     pub const ROOT_ID: Oid = Oid::from_bytes([
-        12, 46, 2, 176, 91, 76, 242, 95, 187, 36, 182, 14, 106, 55, 234, 69, 19, 82, 131, 152, 198,
-        253, 24, 229, 177, 158, 229, 37, 115, 159, 138, 217,
+        5, 253, 166, 247, 170, 213, 15, 254, 238, 76, 36, 112, 226, 76, 72, 161, 232, 248, 68, 233,
+        17, 70, 59, 41, 225, 8, 191, 185, 221, 60, 244, 94,
     ]);
     pub const ROOT: Self = Commit {
         id: Self::ROOT_ID,
         data: CommitData::ROOT,
     };
 }
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::{Commit, CommitData};
+
+    #[test]
+    fn test_serde() {
+        let json = serde_json::to_value(Commit::ROOT).unwrap();
+        assert_eq!(json["id"], Commit::ROOT_ID.to_hex_string());
+        assert_eq!(json["data"]["parent"], serde_json::Value::Null);
+        assert_eq!(json["data"]["date"], "1970-01-01T00:00:00Z");
+
+        let commit: Commit = serde_json::from_value(json).unwrap();
+        assert_eq!(commit.id(), Commit::ROOT_ID);
+        assert_eq!(commit.data().date(), CommitData::ROOT.date());
+    }
+
+    #[test]
+    fn test_deserialize_validates_headers() {
+        let mut json = serde_json::to_value(Commit::ROOT).unwrap();
+        json["data"]["headers"] = serde_json::json!([["Ticket", "ABC-123"]]);
+        let commit: Commit = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(commit.data().headers().len(), 1);
+
+        json["data"]["headers"] = serde_json::json!([["Not a key", "ABC-123"]]);
+        assert!(serde_json::from_value::<Commit>(json.clone()).is_err());
+        json["data"]["headers"] = serde_json::json!([["Ticket", "a\0b"]]);
+        assert!(serde_json::from_value::<Commit>(json).is_err());
+    }
+}
@@ -1,4 +1,4 @@
-use std::string::FromUtf8Error;
+use std::{str::Utf8Error, string::FromUtf8Error};
 
 use braid_hash::Oid;
 use thiserror::Error;
@@ -13,6 +13,9 @@ pub enum Error {
     #[error("Invalid entry name: {0}")]
     FromUtf8(#[from] FromUtf8Error),
 
+    #[error("Invalid entry name: {0}")]
+    Utf8(#[from] Utf8Error),
+
     #[error("Unable to validate oid against kind `{0:?}`: {1}")]
     ObjectNotFound(ObjectKind, Oid),
 
@@ -31,6 +34,48 @@ pub enum Error {
         actual: ObjectKind,
     },
 
+    #[error("Object hash mismatch: expected {expected}, but got {actual}")]
+    HashMismatch { expected: Oid, actual: Oid },
+
+    #[error("Unsupported format version {version} for object kind `{kind:?}`")]
+    UnsupportedFormatVersion { kind: ObjectKind, version: u8 },
+
+    #[error("String exceeds the limit of {limit} characters")]
+    StringTooLong { limit: usize },
+
+    #[error("Register has {count} entries, exceeding the limit of {limit}")]
+    TooManyEntries { count: usize, limit: usize },
+
+    #[error("Object header declares {declared} bytes, but got {actual}")]
+    InvalidDataSize { declared: usize, actual: usize },
+
+    #[error("{0} unexpected trailing bytes after object data")]
+    TrailingData(usize),
+
+    #[error("Objects of kind `{0:?}` have no canonical encoding")]
+    UnencodedKind(ObjectKind),
+
+    #[error("Non-canonical object encoding: {0}")]
+    NonCanonical(&'static str),
+
+    #[error("Invalid commit header key: {0:?}")]
+    InvalidHeaderKey(String),
+
+    #[error("String contains a NUL byte: {0:?}")]
+    NulInString(String),
+
+    #[error("Invalid bundle: {0}")]
+    InvalidBundle(&'static str),
+
+    #[error(transparent)]
+    UnknownHashAlgorithm(#[from] braid_hash::UnknownHashAlgorithmError),
+
+    #[error("Expected hash scheme {expected}, but the repository uses {actual}")]
+    HashSchemeMismatch {
+        expected: braid_hash::HashScheme,
+        actual: braid_hash::HashScheme,
+    },
+
     #[error(transparent)]
     InvalidCharacterInKey(#[from] crate::key::InvalidCharacterInKeyError),
 
@@ -38,6 +83,33 @@ pub enum Error {
     #[error("Unhandled RocksDB error: {0}")]
     RocksDbError(#[from] rocksdb::Error),
 
+    #[cfg(feature = "signing")]
+    #[error("Invalid signature")]
+    InvalidSignature,
+
+    #[cfg(feature = "signing")]
+    #[error("Ref `{refname}` requires a signed commit, but {oid} is not signed")]
+    UnsignedCommit { refname: String, oid: Oid },
+
+    #[cfg(feature = "signing")]
+    #[error("Ref `{refname}` is protected, but no signing key is trusted")]
+    NoTrustedKeys { refname: String },
+
+    #[cfg(feature = "git")]
+    #[error("Unhandled git error: {0}")]
+    Git(#[from] git2::Error),
+
+    #[cfg(feature = "git")]
+    #[error("Git commit {commit} has parent {parent} outside the imported history")]
+    GitParentMissing {
+        commit: git2::Oid,
+        parent: git2::Oid,
+    },
+
+    #[cfg(feature = "git")]
+    #[error("Git tree {tree} has more than one entry named {name:?} after UTF-8 conversion")]
+    GitNameCollision { tree: git2::Oid, name: String },
+
     #[cfg(feature = "postgres")]
     #[error("Unhandled Postgres error: {0}")]
     Postgres(sqlx::Error),
@@ -45,4 +117,43 @@ pub enum Error {
     #[cfg(feature = "postgres")]
     #[error("Postgres backend already initialized")]
     PostgresBackendAlreadyInitialized,
+
+    #[cfg(feature = "postgres")]
+    #[error("Postgres backend not initialized")]
+    PostgresBackendNotInitialized,
+
+    #[cfg(feature = "postgres")]
+    #[error("Postgres schema version {version} is newer than the supported {supported}")]
+    SchemaTooNew { version: i32, supported: i32 },
+
+    #[cfg(feature = "postgres")]
+    #[error("Referenced object {oid} of kind `{kind:?}` does not exist")]
+    MissingReference {
+        oid: Oid,
+        /// `None` when the reference may point to more than one kind.
+        kind: Option<ObjectKind>,
+    },
+
+    #[cfg(feature = "postgres")]
+    #[error("Object {oid} already exists as `{existing:?}`, not `{requested:?}`")]
+    ObjectKindConflict {
+        oid: Oid,
+        existing: ObjectKind,
+        requested: ObjectKind,
+    },
+
+    #[cfg(feature = "postgres")]
+    #[error("Unique constraint `{constraint}` violated")]
+    UniqueViolation {
+        constraint: String,
+        oid: Option<Oid>,
+    },
+
+    #[cfg(feature = "postgres")]
+    #[error("Postgres serialization failure, the transaction can be retried")]
+    SerializationFailure,
+
+    #[cfg(feature = "postgres")]
+    #[error("Postgres detected a deadlock, the transaction can be retried")]
+    Deadlock,
 }
@@ -0,0 +1,258 @@
+//! A writer for `git fast-import` streams.
+//!
+//! Blobs and commits are referred to by marks, which the writer hands out in
+//! order. Every file is written with mode `100644`, braid has no file modes.
+
+use std::{fmt, io::Write};
+
+use crate::{
+    commit::{CommitData, Identity},
+    Result,
+};
+
+/// A reference to a blob or commit written earlier in the stream.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Mark(u64);
+
+impl fmt::Display for Mark {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, ":{}", self.0)
+    }
+}
+
+/// A change to a single path, relative to the commit's first parent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileChange {
+    Modify {
+        path: String,
+        blob: Mark,
+    },
+    /// Deletes a file or a directory with everything in it.
+    Delete {
+        path: String,
+    },
+}
+
+pub struct FastExportWriter<W: Write> {
+    writer: W,
+    next_mark: u64,
+}
+
+impl<W: Write> FastExportWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            next_mark: 1,
+        }
+    }
+
+    pub fn blob(&mut self, bytes: &[u8]) -> Result<Mark> {
+        let mark = self.mark();
+        writeln!(self.writer, "blob")?;
+        writeln!(self.writer, "mark {mark}")?;
+        self.data(bytes)?;
+        Ok(mark)
+    }
+
+    /// Writes a commit onto `refname`. Every blob in `changes` must already
+    /// have been written.
+    pub fn commit<S: AsRef<str>>(
+        &mut self,
+        refname: &str,
+        data: &CommitData<S>,
+        from: Option<Mark>,
+        merge: Option<Mark>,
+        changes: &[FileChange],
+    ) -> Result<Mark> {
+        let mark = self.mark();
+        writeln!(self.writer, "commit {refname}")?;
+        writeln!(self.writer, "mark {mark}")?;
+        writeln!(
+            self.writer,
+            "author {} {}",
+            identity(data.author()),
+            date(data.author_date())
+        )?;
+        writeln!(
+            self.writer,
+            "committer {} {}",
+            identity(data.committer()),
+            date(data.date())
+        )?;
+
+        let mut message = data.summary().as_ref().to_string();
+        if !data.body().as_ref().is_empty() {
+            message.push_str("\n\n");
+            message.push_str(data.body().as_ref());
+        }
+        message.push('\n');
+        self.data(message.as_bytes())?;
+
+        if let Some(from) = from {
+            writeln!(self.writer, "from {from}")?;
+        }
+        if let Some(merge) = merge {
+            writeln!(self.writer, "merge {merge}")?;
+        }
+
+        for change in changes {
+            match change {
+                FileChange::Modify { path, blob } => {
+                    writeln!(self.writer, "M 100644 {blob} {}", quote(path))?
+                }
+                FileChange::Delete { path } => writeln!(self.writer, "D {}", quote(path))?,
+            }
+        }
+        writeln!(self.writer)?;
+
+        Ok(mark)
+    }
+
+    /// Points `refname` at a commit.
+    pub fn reset(&mut self, refname: &str, commit: Mark) -> Result<()> {
+        writeln!(self.writer, "reset {refname}")?;
+        writeln!(self.writer, "from {commit}")?;
+        writeln!(self.writer)?;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn mark(&mut self) -> Mark {
+        let mark = Mark(self.next_mark);
+        self.next_mark += 1;
+        mark
+    }
+
+    fn data(&mut self, bytes: &[u8]) -> Result<()> {
+        writeln!(self.writer, "data {}", bytes.len())?;
+        self.writer.write_all(bytes)?;
+        writeln!(self.writer)?;
+        Ok(())
+    }
+}
+
+/// git requires `Name <email>`. Commits from before identities had an email
+/// may already hold both in the name.
+fn identity<S: AsRef<str>>(identity: &Identity<S>) -> String {
+    let name = identity.name().as_ref().trim();
+    let (name, email) = match identity.email().as_ref() {
+        "" => name
+            .strip_suffix('>')
+            .and_then(|name| name.rsplit_once('<'))
+            .unwrap_or((name, "")),
+        email => (name, email),
+    };
+    format!("{} <{}>", clean(name), clean(email))
+}
+
+/// Drops the characters that would end a name or email early, a newline
+/// becomes a space.
+fn clean(part: &str) -> String {
+    part.chars()
+        .filter(|c| !matches!(c, '<' | '>'))
+        .map(|c| if c == '\n' { ' ' } else { c })
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+fn date(date: time::OffsetDateTime) -> String {
+    let offset = date.offset();
+    let sign = if offset.is_negative() { '-' } else { '+' };
+    format!(
+        "{} {sign}{:02}{:02}",
+        date.unix_timestamp(),
+        offset.whole_hours().abs(),
+        offset.minutes_past_hour().abs()
+    )
+}
+
+/// Quotes paths that git would otherwise misread.
+fn quote(path: &str) -> String {
+    if !path.starts_with('"') && !path.contains(['\n', '\\']) {
+        return path.to_string();
+    }
+
+    let mut quoted = String::with_capacity(path.len() + 2);
+    quoted.push('"');
+    for c in path.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FastExportWriter, FileChange};
+    use crate::commit::{Commit, CommitData, Identity};
+
+    #[test]
+    fn test_stream() {
+        let date = time::OffsetDateTime::from_unix_timestamp(1_700_000_000)
+            .unwrap()
+            .to_offset(time::UtcOffset::from_hms(-5, -30, 0).unwrap());
+        let data = CommitData::new(
+            Commit::ROOT_ID,
+            Commit::ROOT_ID,
+            None,
+            None,
+            Commit::ROOT_ID,
+            date,
+            Identity::new("Ada", ""),
+            "Add files",
+            "",
+        );
+
+        let mut writer = FastExportWriter::new(Vec::new());
+        let blob = writer.blob(b"hi").unwrap();
+        let first = writer
+            .commit("refs/heads/main", &data, None, None, &[])
+            .unwrap();
+        let changes = [
+            FileChange::Modify {
+                path: "dir/a b".to_string(),
+                blob,
+            },
+            FileChange::Delete {
+                path: "\"odd".to_string(),
+            },
+        ];
+        let second = writer
+            .commit("refs/heads/main", &data, Some(first), None, &changes)
+            .unwrap();
+        writer.reset("refs/tags/v1", second).unwrap();
+
+        let stream = String::from_utf8(writer.finish().unwrap()).unwrap();
+        let expected = "blob\nmark :1\ndata 2\nhi\n\
+            commit refs/heads/main\nmark :2\nauthor Ada <> 1700000000 -0530\ncommitter Ada <> 1700000000 -0530\ndata 10\nAdd files\n\n\n\
+            commit refs/heads/main\nmark :3\nauthor Ada <> 1700000000 -0530\ncommitter Ada <> 1700000000 -0530\ndata 10\nAdd files\n\n\
+            from :2\nM 100644 :1 dir/a b\nD \"\\\"odd\"\n\n\
+            reset refs/tags/v1\nfrom :3\n\n";
+        assert_eq!(stream, expected);
+    }
+
+    #[test]
+    fn test_identity() {
+        let identity = |name, email| super::identity(&Identity::new(name, email));
+        assert_eq!(identity("Ada", "ada@example.com"), "Ada <ada@example.com>");
+        assert_eq!(
+            identity("Ada <ada@example.com>", ""),
+            "Ada <ada@example.com>"
+        );
+        assert_eq!(identity("Ada", ""), "Ada <>");
+        assert_eq!(
+            identity("A<d>a\nL", "a>da@\nexample.com"),
+            "Ada L <ada@ example.com>"
+        );
+    }
+}
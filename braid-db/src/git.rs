@@ -0,0 +1,494 @@
+//! Imports the history of a git repository as braid objects.
+//!
+//! Blobs become content, trees become nested registers and commits become
+//! [`CommitData`] with their first two parents as `parent` and `merge_parent`
+//! and the same author and committer. Branches and tags pointing at commits
+//! become refs under their full git name.
+//!
+//! Submodules, file modes and any parents beyond the second are not
+//! representable and are dropped. The oldest commits of a shallow clone have
+//! no parents in git and start from the root commit.
+//!
+//! Names, emails, messages and file names that are not UTF-8 are converted
+//! lossily, replacing invalid sequences with U+FFFD. A tree with two file
+//! names that convert to the same key fails the import.
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    path::Path,
+};
+
+use braid_hash::{HashScheme, Oid};
+use git2::{ObjectType, Repository, Sort};
+
+use crate::{
+    bundle::{Entry, Ref},
+    commit::{CommitData, Identity},
+    oid::{CommitOid, SaveRegisterOid, ValidOid},
+    register::RegisterData,
+    Error, Hash, Object, ObjectData, RegisterEntryKey, Result,
+};
+
+/// Converts a git repository into bundle entries in dependency order.
+///
+/// Every entry only references entries yielded before it or the root commit.
+/// The entries can be written to a bundle or imported into a store directly.
+pub struct GitImporter {
+    repo: Repository,
+    commits: std::vec::IntoIter<git2::Oid>,
+    tips: Vec<(String, git2::Oid)>,
+    state: State,
+}
+
+/// Kept apart from the repository, which the git objects being converted borrow.
+struct State {
+    scheme: HashScheme,
+    root: Oid,
+    empty_saves: Oid,
+    converted: HashMap<git2::Oid, Oid>,
+    emitted: HashSet<Oid>,
+    pending: VecDeque<Entry>,
+}
+
+impl GitImporter {
+    /// Opens a bare repository or the repository of a working tree, whose
+    /// objects are hashed with `scheme`, which must be the target's.
+    pub fn open(path: impl AsRef<Path>, scheme: HashScheme) -> Result<Self> {
+        let repo = Repository::open(path)?;
+
+        let mut tips = Vec::new();
+        for reference in repo.references()? {
+            let reference = reference?;
+            let Some(name) = reference.name() else {
+                continue;
+            };
+            if !(name.starts_with("refs/heads/") || name.starts_with("refs/tags/")) {
+                continue;
+            }
+            if let Ok(commit) = reference.peel_to_commit() {
+                tips.push((name.to_string(), commit.id()));
+            }
+        }
+
+        let mut walk = repo.revwalk()?;
+        walk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)?;
+        for (_, tip) in &tips {
+            walk.push(*tip)?;
+        }
+        let commits = walk.collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            repo,
+            commits: commits.into_iter(),
+            tips,
+            state: State {
+                scheme,
+                root: CommitOid::root(scheme)?.oid(),
+                empty_saves: SaveRegisterOid::empty(scheme)?.oid(),
+                converted: HashMap::new(),
+                emitted: HashSet::new(),
+                pending: VecDeque::new(),
+            },
+        })
+    }
+
+    /// The branches and tags whose commits have been converted so far.
+    ///
+    /// Once every entry has been read this contains all of them.
+    pub fn refs(&self) -> Vec<Ref> {
+        self.tips
+            .iter()
+            .filter_map(|(name, tip)| {
+                self.state.converted.get(tip).map(|oid| Ref {
+                    name: name.clone(),
+                    oid: *oid,
+                })
+            })
+            .collect()
+    }
+
+    pub fn next_entry(&mut self) -> Result<Option<Entry>> {
+        loop {
+            if let Some(entry) = self.state.pending.pop_front() {
+                return Ok(Some(entry));
+            }
+
+            match self.commits.next() {
+                Some(commit) => self.state.convert_commit(&self.repo, commit)?,
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+impl State {
+    fn convert_commit(&mut self, repo: &Repository, id: git2::Oid) -> Result<()> {
+        let commit = repo.find_commit(id)?;
+        let register = self.convert_tree(repo, &commit.tree()?)?;
+
+        let parents = commit.parent_ids().map(|parent| {
+            // the walk converts parents first, unless git hides one from it
+            self.converted
+                .get(&parent)
+                .copied()
+                .ok_or(Error::GitParentMissing { commit: id, parent })
+        });
+        let mut parents = parents.collect::<Result<Vec<_>>>()?.into_iter();
+        let parent = parents.next().unwrap_or(self.root);
+        let merge_parent = parents.next();
+
+        let (author, author_date) = identity(&commit.author())?;
+        let (committer, date) = identity(&commit.committer())?;
+
+        let message = String::from_utf8_lossy(commit.message_bytes());
+        let (summary, body) = split_message(&message);
+
+        let data = CommitData::new(
+            register,
+            parent,
+            merge_parent,
+            None,
+            self.empty_saves,
+            date,
+            committer,
+            summary,
+            body,
+        )
+        .with_author(author, author_date);
+        self.push(id, ObjectData::Commit(data)).map(|_| ())
+    }
+
+    fn convert_tree(&mut self, repo: &Repository, tree: &git2::Tree) -> Result<Oid> {
+        if let Some(oid) = self.converted.get(&tree.id()) {
+            return Ok(*oid);
+        }
+
+        let mut data = RegisterData::new();
+        for entry in tree.iter() {
+            let oid = match entry.kind() {
+                Some(ObjectType::Blob) => self.convert_blob(repo, entry.id())?,
+                Some(ObjectType::Tree) => self.convert_tree(repo, &repo.find_tree(entry.id())?)?,
+                _ => continue,
+            };
+
+            let name = String::from_utf8_lossy(entry.name_bytes()).into_owned();
+            if data.get(name.as_str()).is_some() {
+                return Err(Error::GitNameCollision {
+                    tree: tree.id(),
+                    name,
+                });
+            }
+            data.insert(RegisterEntryKey::try_from(name)?, oid);
+        }
+
+        self.push(tree.id(), ObjectData::Register(data))
+    }
+
+    fn convert_blob(&mut self, repo: &Repository, id: git2::Oid) -> Result<Oid> {
+        if let Some(oid) = self.converted.get(&id) {
+            return Ok(*oid);
+        }
+
+        let blob = repo.find_blob(id)?;
+        let bytes = blob.content().to_vec();
+        let oid = crate::content_oid(self.scheme, &bytes);
+
+        self.converted.insert(id, oid);
+        if self.emitted.insert(oid) {
+            self.pending.push_back(Entry::Content { oid, bytes });
+        }
+        Ok(oid)
+    }
+
+    fn push(&mut self, id: git2::Oid, data: ObjectData) -> Result<Oid> {
+        let (oid, encoded) = match &data {
+            ObjectData::Commit(data) => data.hash_with(self.scheme)?,
+            ObjectData::Save(data) => data.hash_with(self.scheme)?,
+            ObjectData::Register(data) => data.hash_with(self.scheme)?,
+            ObjectData::SaveRegister(data) => data.hash_with(self.scheme)?,
+        };
+
+        self.converted.insert(id, oid);
+        if self.emitted.insert(oid) {
+            let object = Object::from_encoded(oid, &encoded)?;
+            self.pending.push_back(Entry::Object { object, data });
+        }
+        Ok(oid)
+    }
+}
+
+impl Iterator for GitImporter {
+    type Item = Result<Entry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_entry().transpose()
+    }
+}
+
+fn identity(signature: &git2::Signature) -> Result<(Identity, time::OffsetDateTime)> {
+    let when = signature.when();
+    let offset = time::UtcOffset::from_whole_seconds(when.offset_minutes() * 60)
+        .map_err(Error::InvalidOffset)?;
+    let date = time::OffsetDateTime::from_unix_timestamp(when.seconds())
+        .map_err(Error::InvalidTimestamp)?
+        .to_offset(offset);
+
+    let identity = Identity::new(
+        String::from_utf8_lossy(signature.name_bytes()).into_owned(),
+        String::from_utf8_lossy(signature.email_bytes()).into_owned(),
+    );
+    Ok((identity, date))
+}
+
+/// Splits a git message into its first paragraph and the rest.
+fn split_message(message: &str) -> (String, String) {
+    let message = message.trim();
+    match message.split_once("\n\n") {
+        Some((summary, body)) => (summary.trim().to_string(), body.trim().to_string()),
+        None => (message.to_string(), String::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, path::Path};
+
+    use braid_hash::{HashAlgorithm, HashScheme};
+    use git2::{Repository, Signature, Time};
+
+    use super::GitImporter;
+    use crate::{
+        bundle::Entry,
+        oid::{CommitOid, ValidOid},
+        Error, ObjectData,
+    };
+
+    #[cfg(feature = "postgres")]
+    struct Contents(std::collections::HashMap<braid_hash::Oid, Vec<u8>>);
+
+    #[cfg(feature = "postgres")]
+    impl crate::bundle::ContentSink for Contents {
+        fn write_content(&mut self, oid: braid_hash::Oid, bytes: &[u8]) -> std::io::Result<()> {
+            self.0.insert(oid, bytes.to_vec());
+            Ok(())
+        }
+    }
+
+    fn commit(repo: &Repository, files: &[(&str, &str)], message: &str) -> git2::Oid {
+        let root = repo.workdir().unwrap();
+        for (path, content) in files {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+
+        let mut index = repo.index().unwrap();
+        for (path, _) in files {
+            index.add_path(Path::new(path)).unwrap();
+        }
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+
+        let author =
+            Signature::new("Ada", "ada@example.com", &Time::new(1_700_000_000, 60)).unwrap();
+        let committer =
+            Signature::new("Bob", "bob@example.com", &Time::new(1_700_000_100, 0)).unwrap();
+        let parent = repo.head().ok().map(|head| head.peel_to_commit().unwrap());
+        let parents: Vec<_> = parent.iter().collect();
+        repo.commit(Some("HEAD"), &author, &committer, message, &tree, &parents)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_import() {
+        let dir = tempdir::TempDir::new("braid-git").unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+
+        commit(&repo, &[("a.txt", "a"), ("src/b.txt", "b")], "first");
+        let head = commit(&repo, &[("src/b.txt", "c")], "second\n\nmore\n");
+        repo.tag_lightweight("v1", &repo.find_object(head, None).unwrap(), false)
+            .unwrap();
+
+        let branch = repo.head().unwrap().name().unwrap().to_string();
+        let scheme = HashScheme::default();
+        let root = CommitOid::root(scheme).unwrap().oid();
+        let mut importer = GitImporter::open(dir.path(), scheme).unwrap();
+        let entries: Vec<Entry> = importer.by_ref().collect::<Result<_, _>>().unwrap();
+
+        let mut seen = HashSet::from([root]);
+        let mut commits = Vec::new();
+        for entry in &entries {
+            if let Entry::Object { data, .. } = entry {
+                let children: Vec<_> = match data {
+                    ObjectData::Commit(data) => {
+                        commits.push(data.clone());
+                        [data.register()].into_iter().chain(data.parent()).collect()
+                    }
+                    ObjectData::Register(data) => data.iter().map(|(_, oid)| *oid).collect(),
+                    _ => unreachable!(),
+                };
+                assert!(children.iter().all(|oid| seen.contains(oid)));
+            }
+            assert!(seen.insert(entry.oid()));
+        }
+
+        // 3 blobs, 4 trees and 2 commits
+        assert_eq!(entries.len(), 9);
+        assert_eq!(commits.len(), 2);
+        assert_eq!(commits[0].parent(), Some(root));
+        assert_eq!(commits[1].summary(), "second");
+        assert_eq!(commits[1].body(), "more");
+        assert_eq!(commits[1].author().to_string(), "Ada <ada@example.com>");
+        assert_eq!(commits[1].author_date().offset().whole_minutes(), 60);
+        assert_eq!(commits[1].committer().to_string(), "Bob <bob@example.com>");
+        assert_eq!(commits[1].date().unix_timestamp(), 1_700_000_100);
+
+        let mut refs = importer.refs();
+        refs.sort_by(|a, b| a.name.cmp(&b.name));
+        let names: Vec<_> = refs.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, [branch.as_str(), "refs/tags/v1"]);
+        assert!(refs.iter().all(|r| r.oid == entries[8].oid()));
+
+        // every oid, including the root parent, follows the scheme
+        let scheme = HashScheme::new(HashAlgorithm::Sha256);
+        let entries: Vec<Entry> = GitImporter::open(dir.path(), scheme)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let Entry::Object {
+            data: ObjectData::Commit(first),
+            ..
+        } = &entries[4]
+        else {
+            panic!("expected a commit");
+        };
+        assert_eq!(first.parent(), Some(CommitOid::root(scheme).unwrap().oid()));
+        assert_eq!(entries[0].oid(), crate::content_oid(scheme, b"a"));
+    }
+
+    #[cfg(feature = "postgres")]
+    #[tokio::test]
+    async fn test_import_postgres() {
+        use crate::postgres::{bundle, fsck, odb, testing};
+
+        let dir = tempdir::TempDir::new("braid-git").unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        commit(&repo, &[("a.txt", "a")], "first");
+        commit(&repo, &[("a.txt", "b")], "second");
+
+        let pool = testing::database("git_import").await;
+        crate::postgres::init(&pool).await.unwrap();
+        let mut conn = pool.acquire().await.unwrap();
+        let scheme = crate::postgres::hash_scheme(&mut *conn).await.unwrap();
+
+        let entries: Vec<Entry> = GitImporter::open(dir.path(), scheme)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let commits: Vec<_> = entries
+            .iter()
+            .filter_map(|entry| match entry {
+                Entry::Object {
+                    data: ObjectData::Commit(data),
+                    ..
+                } => Some((entry.oid(), data.clone())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(commits.len(), 2);
+
+        let mut contents = Contents(Default::default());
+        let entries = entries.into_iter().map(Ok);
+        bundle::import_entries(&mut conn, entries, scheme, &mut contents)
+            .await
+            .unwrap();
+
+        // the author date keeps git's +01:00 offset through Postgres
+        for (oid, data) in &commits {
+            let read = odb::verified::get_commit(*oid, scheme, &mut *conn)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(read.data(), data);
+            assert_eq!(read.data().author_date().offset().whole_minutes(), 60);
+        }
+
+        let report = fsck::verify(&mut conn, commits.iter().map(|(oid, _)| *oid))
+            .await
+            .unwrap();
+        assert!(report.is_ok(), "{:?}", report.issues);
+        assert!(report.unreachable.is_empty());
+    }
+
+    #[test]
+    fn test_shallow() {
+        let dir = tempdir::TempDir::new("braid-git").unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+
+        commit(&repo, &[("a.txt", "a")], "first");
+        let boundary = commit(&repo, &[("a.txt", "b")], "second");
+        commit(&repo, &[("a.txt", "c")], "third");
+        std::fs::write(repo.path().join("shallow"), format!("{boundary}\n")).unwrap();
+
+        let scheme = HashScheme::default();
+        let importer = GitImporter::open(dir.path(), scheme).unwrap();
+        let entries: Vec<Entry> = importer.collect::<Result<_, _>>().unwrap();
+        let commits: Vec<_> = entries
+            .iter()
+            .filter_map(|entry| match entry {
+                Entry::Object {
+                    data: ObjectData::Commit(data),
+                    ..
+                } => Some(data),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(commits.len(), 2);
+        assert_eq!(commits[0].summary(), "second");
+        assert_eq!(
+            commits[0].parent(),
+            Some(CommitOid::root(scheme).unwrap().oid())
+        );
+        assert_eq!(commits[1].parent(), Some(entries[2].oid()));
+    }
+
+    #[test]
+    fn test_file_names() {
+        let dir = tempdir::TempDir::new("braid-git").unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let blob = repo.blob(b"a").unwrap();
+
+        let tree = |names: &[&[u8]]| {
+            let mut builder = repo.treebuilder(None).unwrap();
+            for name in names {
+                builder.insert(name.to_vec(), blob, 0o100644).unwrap();
+            }
+            let tree = repo.find_tree(builder.write().unwrap()).unwrap();
+            let signature =
+                Signature::new("Ada", "ada@example.com", &Time::new(1_700_000_000, 0)).unwrap();
+            let head = repo
+                .commit(None, &signature, &signature, "files", &tree, &[])
+                .unwrap();
+            repo.reference("refs/heads/main", head, true, "").unwrap();
+            GitImporter::open(dir.path(), HashScheme::default())
+                .unwrap()
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        let entries = tree(&[b"caf\xe9"]).unwrap();
+        let Entry::Object {
+            data: ObjectData::Register(register),
+            ..
+        } = &entries[1]
+        else {
+            panic!("expected a register");
+        };
+        assert!(register.get("caf\u{FFFD}").is_some());
+
+        assert!(matches!(
+            tree(&[b"caf\xe9", b"caf\xff"]),
+            Err(Error::GitNameCollision { .. })
+        ));
+    }
+}
@@ -12,10 +12,6 @@ macro_rules! impl_key {
                 self.0
             }
 
-            pub(crate) const fn new_unchecked(key: S) -> Self {
-                Self(key)
-            }
-
             pub const fn as_ref(&self) -> $name<&S> {
                 $name(&self.0)
             }
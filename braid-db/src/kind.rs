@@ -1,9 +1,7 @@
 pub(crate) struct UnmappedKindError(pub(crate) u8);
 
 pub(crate) trait Kind: 'static + Copy + std::cmp::Eq + Sized {
-    const MIN: Self;
     const MAX: Self;
-    const VARIANTS: &'static [Self];
 
     type Error: From<crate::kind::UnmappedKindError>;
 
@@ -17,8 +15,6 @@ pub(crate) trait Kind: 'static + Copy + std::cmp::Eq + Sized {
     {
         Self::from_u8(value).ok_or(UnmappedKindError(value).into())
     }
-
-    fn as_u8(self) -> u8;
 }
 
 macro_rules! kind {
@@ -30,7 +26,7 @@ macro_rules! kind {
 
         $err:ident => $display:expr
     ) => {
-        #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+        #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
         $(#[$meta])*
         $vis enum $name {
             $($variant = $value,)*
@@ -77,17 +73,6 @@ macro_rules! kind {
                     _ => unreachable!(),
                 }
             }
-
-            #[allow(unused_assignments)]
-            const fn variants() -> [Self; Self::MIN_MAX_VALUE.1 as usize + 1] {
-                let mut all = [Self::MIN_MAX_VALUE.0; Self::MIN_MAX_VALUE.1 as usize + 1];
-                let mut i = 0;
-                $(
-                    all[i] = $name::$variant;
-                    i += 1;
-                )*
-                all
-            }
         }
 
         impl From<crate::kind::UnmappedKindError> for $err {
@@ -105,17 +90,11 @@ macro_rules! kind {
 
         impl crate::Kind for $name {
             type Error = $err;
-            const MIN: Self = Self::MIN_MAX_VALUE.0;
             const MAX: Self = Self::MIN_MAX_VALUE.1;
-            const VARIANTS: &'static [Self] = &Self::variants();
 
             fn from_u8(value: u8) -> Option<Self> {
                 Self::try_from_u8(value)
             }
-
-            fn as_u8(self) -> u8 {
-                self as u8
-            }
         }
     };
 }
@@ -3,18 +3,26 @@ mod kind;
 mod bytes;
 mod err;
 
+pub mod bundle;
 pub mod commit;
+pub mod fast_export;
 mod key;
 pub mod oid;
 pub mod register;
 pub mod save;
+#[cfg(feature = "signing")]
+pub mod signing;
 
+#[cfg(feature = "git")]
+pub mod git;
 #[cfg(feature = "postgres")]
 pub mod postgres;
 
+pub use bytes::DecodeLimits;
+pub(crate) use bytes::Hash;
 pub use key::{Key, RegisterEntryKey, SaveEntryKey};
 
-use braid_hash::Oid;
+use braid_hash::{Domain, HashScheme, Oid};
 
 pub type Result<T> = std::result::Result<T, err::Error>;
 
@@ -28,15 +36,35 @@ kind! {
         Commit = 1,
         Save = 2,
         SaveRegister = 3,
+        Content = 4,
     }
 
     ObjectKindError => "Invalid object kind: {0:?}"
 }
 
+impl ObjectKind {
+    /// The domain objects of this kind are hashed in, see [`HashScheme`].
+    pub const fn domain(self) -> Domain {
+        match self {
+            Self::Register => Domain::new("braid 2026-10-18 register"),
+            Self::Commit => Domain::new("braid 2026-10-18 commit"),
+            Self::Save => Domain::new("braid 2026-10-18 save"),
+            Self::SaveRegister => Domain::new("braid 2026-10-18 save register"),
+            Self::Content => Domain::new("braid 2026-10-18 content"),
+        }
+    }
+}
+
+/// The oid of content with the bytes `data`.
+pub fn content_oid(scheme: HashScheme, data: &[u8]) -> Oid {
+    scheme.hash(ObjectKind::Content.domain(), data)
+}
+
 #[derive(Debug, Clone)]
 pub struct Object {
     pub(crate) oid: Oid,
     pub(crate) kind: ObjectKind,
+    pub(crate) version: u8,
     pub(crate) size: u32,
 }
 
@@ -49,11 +77,59 @@ impl Object {
         self.kind
     }
 
+    /// The format version of the object's encoding.
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
     pub fn size(&self) -> u32 {
         self.size
     }
 }
 
+/// The decoded data of an object, see [`Object::decode`].
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ObjectData {
+    Commit(commit::CommitData),
+    Save(save::SaveData),
+    Register(register::RegisterData<String>),
+    SaveRegister(register::SaveRegisterData<String>),
+}
+
+impl ObjectData {
+    pub fn kind(&self) -> ObjectKind {
+        match self {
+            Self::Commit(_) => ObjectKind::Commit,
+            Self::Save(_) => ObjectKind::Save,
+            Self::Register(_) => ObjectKind::Register,
+            Self::SaveRegister(_) => ObjectKind::SaveRegister,
+        }
+    }
+}
+
+/// The decoded data of an object, borrowing its strings from the encoding.
+/// See [`Object::decode_borrowed`].
+#[derive(Debug, Clone)]
+#[allow(clippy::large_enum_variant)]
+pub enum ObjectDataRef<'a> {
+    Commit(commit::CommitData<&'a str>),
+    Save(save::SaveData<&'a str>),
+    Register(register::RegisterDataRef<'a>),
+    SaveRegister(register::RegisterDataRef<'a>),
+}
+
+impl ObjectDataRef<'_> {
+    pub fn kind(&self) -> ObjectKind {
+        match self {
+            Self::Commit(_) => ObjectKind::Commit,
+            Self::Save(_) => ObjectKind::Save,
+            Self::Register(_) => ObjectKind::Register,
+            Self::SaveRegister(_) => ObjectKind::SaveRegister,
+        }
+    }
+}
+
 pub(crate) mod sealed {
     pub trait Sealed {}
 
@@ -1,13 +1,22 @@
-use braid_hash::Oid;
+use braid_hash::{HashScheme, Oid};
 
-use crate::ObjectKind;
+use crate::{
+    bytes::Hash,
+    commit::CommitData,
+    register::{RegisterData, SaveRegisterData},
+    ObjectKind, Result,
+};
 
+/// An oid that is known to exist in a store with the kind `KIND`.
 pub trait ValidOid: sealed::ValidOid {
     const KIND: ObjectKind;
 
     fn oid(&self) -> Oid;
 }
 
+/// An oid that may be used as the parent of a save.
+pub trait SaveParentOid: ValidOid {}
+
 pub(crate) mod sealed {
     pub trait ValidOid {
         fn new(oid: braid_hash::Oid) -> Self;
@@ -16,6 +25,7 @@ pub(crate) mod sealed {
 
 macro_rules! impl_validated_oid {
     ($name:ident ($kind:ident)) => {
+        #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
         pub struct $name(Oid);
 
         impl sealed::ValidOid for $name {
@@ -31,6 +41,12 @@ macro_rules! impl_validated_oid {
                 self.0
             }
         }
+
+        impl From<$name> for Oid {
+            fn from(oid: $name) -> Self {
+                oid.0
+            }
+        }
     };
 }
 
@@ -38,3 +54,27 @@ impl_validated_oid!(CommitOid(Commit));
 impl_validated_oid!(RegisterOid(Register));
 impl_validated_oid!(SaveOid(Save));
 impl_validated_oid!(SaveRegisterOid(SaveRegister));
+impl_validated_oid!(ContentOid(Content));
+
+impl SaveParentOid for CommitOid {}
+impl SaveParentOid for SaveOid {}
+
+// these objects are created when a store is initialized, their oids depend
+// on the hash scheme of the store
+impl CommitOid {
+    pub fn root(scheme: HashScheme) -> Result<Self> {
+        Ok(Self(CommitData::root(scheme)?.hash_with(scheme)?.0))
+    }
+}
+
+impl RegisterOid {
+    pub fn empty(scheme: HashScheme) -> Result<Self> {
+        Ok(Self(RegisterData::<&str>::new().hash_with(scheme)?.0))
+    }
+}
+
+impl SaveRegisterOid {
+    pub fn empty(scheme: HashScheme) -> Result<Self> {
+        Ok(Self(SaveRegisterData::<&str>::new().hash_with(scheme)?.0))
+    }
+}
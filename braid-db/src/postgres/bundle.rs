@@ -0,0 +1,340 @@
+use std::{
+    collections::HashSet,
+    io::{Read, Write},
+};
+
+use braid_hash::{HashScheme, Oid};
+use sqlx::PgConnection;
+
+use crate::{
+    bundle::{BundleReader, BundleWriter, ContentSink, ContentSource, Entry, Ref},
+    Error, Object, ObjectData, ObjectKind, Result,
+};
+
+use super::odb;
+
+/// Writes every object reachable from `refs` into a bundle.
+///
+/// Objects reachable from `haves` are left out, so the bundle only contains
+/// what a receiver that already has those commits is missing. The bytes of
+/// content objects are read from `content`. The bundle records the
+/// repository's hash scheme.
+pub async fn export<W: Write>(
+    conn: &mut PgConnection,
+    refs: &[Ref],
+    haves: &[Oid],
+    content: &mut impl ContentSource,
+    out: W,
+) -> Result<W> {
+    let scheme = super::hash_scheme(&mut *conn).await?;
+
+    let mut seen = HashSet::new();
+    let mut walk = Walk::new(haves.iter().copied(), scheme);
+    while walk.next(&mut seen, conn).await?.is_some() {}
+
+    let mut writer = BundleWriter::new(out, scheme, refs)?;
+    let mut walk = Walk::new(refs.iter().map(|r| r.oid), scheme);
+    while let Some(step) = walk.next(&mut seen, conn).await? {
+        match step {
+            Step::Content(oid) => writer.write_content(oid, &content.read_content(oid)?)?,
+            Step::Object(oid, encoded) => writer.write_object(oid, &encoded)?,
+        }
+    }
+
+    writer.finish()
+}
+
+/// Reads a bundle into the database and returns its refs.
+///
+/// The bundle must have the repository's hash scheme. Every entry is checked
+/// against its oid before it is written, content bytes are handed to
+/// `content`. Objects that already exist are skipped. Run this inside a
+/// transaction so a corrupt bundle leaves no partial graph behind.
+pub async fn import(
+    conn: &mut PgConnection,
+    input: impl Read,
+    content: &mut impl ContentSink,
+) -> Result<Vec<Ref>> {
+    let mut reader = BundleReader::new(input)?;
+    let scheme = reader.scheme();
+    import_entries(conn, &mut reader, scheme, content).await?;
+
+    let refs = reader.refs().to_vec();
+    for r in &refs {
+        if !exists(r.oid, &mut *conn).await? {
+            return Err(Error::MissingReference {
+                oid: r.oid,
+                kind: None,
+            });
+        }
+    }
+
+    Ok(refs)
+}
+
+/// Writes entries in dependency order, such as those of a [`BundleReader`],
+/// whose oids are hashed with `scheme`, which must be the repository's.
+///
+/// Entries are written in batches with a [`BulkWriter`](odb::bulk::BulkWriter),
+/// content of a batch that already exists is not handed to `content` again.
+pub async fn import_entries(
+    conn: &mut PgConnection,
+    mut entries: impl Iterator<Item = Result<Entry>>,
+    scheme: HashScheme,
+    content: &mut impl ContentSink,
+) -> Result<()> {
+    let expected = super::hash_scheme(&mut *conn).await?;
+    if scheme != expected {
+        return Err(Error::HashSchemeMismatch {
+            expected,
+            actual: scheme,
+        });
+    }
+    let mut writer = odb::bulk::BulkWriter::new(scheme);
+
+    loop {
+        let batch = next_batch(
+            &mut entries,
+            odb::bulk::DEFAULT_BATCH_SIZE,
+            BATCH_CONTENT_BYTES,
+        )?;
+        if batch.is_empty() {
+            return Ok(());
+        }
+
+        let oids: Vec<Oid> = batch.iter().map(Entry::oid).collect();
+        let existing = odb::existing(&oids, &mut *conn).await?;
+
+        for entry in batch {
+            if existing.contains(&entry.oid()) {
+                continue;
+            }
+
+            let expected = entry.oid();
+            let actual = match entry {
+                Entry::Content { oid, bytes } => {
+                    let actual = writer.add_content(&bytes)?;
+                    if actual == oid {
+                        content.write_content(oid, &bytes)?;
+                    }
+                    actual
+                }
+                Entry::Object { data, .. } => match &data {
+                    ObjectData::Commit(data) => writer.add(data)?,
+                    ObjectData::Save(data) => writer.add(data)?,
+                    ObjectData::Register(data) => writer.add(data)?,
+                    ObjectData::SaveRegister(data) => writer.add(data)?,
+                },
+            };
+
+            if actual != expected {
+                return Err(Error::HashMismatch { expected, actual });
+            }
+        }
+
+        writer.flush(conn).await?;
+    }
+}
+
+/// Content bytes buffered per batch of [`import_entries`].
+const BATCH_CONTENT_BYTES: usize = 64 << 20;
+
+/// Takes up to `len` entries, stopping early once the content taken reaches
+/// `content_bytes`.
+fn next_batch(
+    entries: &mut impl Iterator<Item = Result<Entry>>,
+    len: usize,
+    content_bytes: usize,
+) -> Result<Vec<Entry>> {
+    let mut batch = Vec::new();
+    let mut bytes = 0;
+
+    while batch.len() < len && bytes < content_bytes {
+        let Some(entry) = entries.next().transpose()? else {
+            break;
+        };
+        if let Entry::Content { bytes: content, .. } = &entry {
+            bytes += content.len();
+        }
+        batch.push(entry);
+    }
+
+    Ok(batch)
+}
+
+async fn exists(oid: Oid, conn: &mut PgConnection) -> Result<bool> {
+    let exists = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM braid.object WHERE id = $1)")
+        .bind(oid)
+        .fetch_one(conn)
+        .await?;
+    Ok(exists)
+}
+
+enum Visit {
+    Enter(Oid),
+    Leave(Oid, Vec<u8>),
+}
+
+pub(super) enum Step {
+    Content(Oid),
+    Object(Oid, Vec<u8>),
+}
+
+/// A depth first walk that yields objects after everything they reference.
+/// Every object is verified against its oid under `scheme`.
+pub(super) struct Walk {
+    stack: Vec<Visit>,
+    scheme: HashScheme,
+}
+
+impl Walk {
+    pub(super) fn new(roots: impl Iterator<Item = Oid>, scheme: HashScheme) -> Self {
+        Self {
+            stack: roots.map(Visit::Enter).collect(),
+            scheme,
+        }
+    }
+
+    /// Yields the next object not in `seen` and adds it to `seen`.
+    pub(super) async fn next(
+        &mut self,
+        seen: &mut HashSet<Oid>,
+        conn: &mut PgConnection,
+    ) -> Result<Option<Step>> {
+        while let Some(visit) = self.stack.pop() {
+            let oid = match visit {
+                Visit::Leave(oid, encoded) => return Ok(Some(Step::Object(oid, encoded))),
+                Visit::Enter(oid) => oid,
+            };
+
+            if !seen.insert(oid) {
+                continue;
+            }
+
+            let encoded = match odb::verified::read_raw(oid, self.scheme, &mut *conn).await {
+                Ok(Some((_, encoded))) => encoded,
+                Ok(None) => return Err(Error::MissingReference { oid, kind: None }),
+                Err(Error::UnencodedKind(ObjectKind::Content)) => {
+                    return Ok(Some(Step::Content(oid)))
+                }
+                Err(err) => return Err(err),
+            };
+
+            let (_, data) = Object::decode(&encoded, self.scheme)?;
+            self.stack.push(Visit::Leave(oid, encoded));
+            self.push_children(&data);
+        }
+
+        Ok(None)
+    }
+
+    fn push_children(&mut self, data: &ObjectData) {
+        let children: Vec<Oid> = match data {
+            ObjectData::Commit(commit) => [commit.register(), commit.saves()]
+                .into_iter()
+                .chain(commit.parent())
+                .chain(commit.merge_parent())
+                .chain(commit.rebase_of())
+                .collect(),
+            ObjectData::Save(save) => vec![save.content(), save.parent()],
+            ObjectData::Register(register) => register.iter().map(|(_, oid)| *oid).collect(),
+            ObjectData::SaveRegister(register) => register.iter().map(|(_, oid)| *oid).collect(),
+        };
+
+        self.stack
+            .extend(children.into_iter().rev().map(Visit::Enter));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use braid_hash::Oid;
+
+    use super::next_batch;
+    use crate::{
+        bundle::{ContentSource, Entry, Ref},
+        postgres::{odb, testing},
+        register::RegisterData,
+        Error, RegisterEntryKey, Result,
+    };
+
+    struct Contents;
+
+    impl ContentSource for Contents {
+        fn read_content(&mut self, _: Oid) -> std::io::Result<Vec<u8>> {
+            Ok(b"a".to_vec())
+        }
+    }
+
+    fn content(len: usize) -> Result<Entry> {
+        Ok(Entry::Content {
+            oid: Oid::repeat(len as u8),
+            bytes: vec![0; len],
+        })
+    }
+
+    #[test]
+    fn test_next_batch() {
+        let lens = |batch: Vec<Entry>| -> Vec<usize> {
+            batch
+                .iter()
+                .map(|entry| match entry {
+                    Entry::Content { bytes, .. } => bytes.len(),
+                    Entry::Object { .. } => unreachable!(),
+                })
+                .collect()
+        };
+
+        // a batch ends with the entry that reaches the byte limit
+        let mut entries = [3, 4, 5, 1, 1, 1].map(content).into_iter();
+        assert_eq!(lens(next_batch(&mut entries, 10, 7).unwrap()), [3, 4]);
+        assert_eq!(lens(next_batch(&mut entries, 10, 7).unwrap()), [5, 1, 1]);
+        assert_eq!(lens(next_batch(&mut entries, 10, 7).unwrap()), [1]);
+        assert!(next_batch(&mut entries, 10, 7).unwrap().is_empty());
+
+        // content larger than the limit gets a batch of its own
+        let mut entries = [20, 1].map(content).into_iter();
+        assert_eq!(lens(next_batch(&mut entries, 10, 7).unwrap()), [20]);
+
+        let mut entries = [1, 1, 1].map(content).into_iter();
+        assert_eq!(lens(next_batch(&mut entries, 2, 7).unwrap()), [1, 1]);
+
+        let err = Error::Io(std::io::ErrorKind::UnexpectedEof.into());
+        let mut entries = [content(1), Err(err)].into_iter();
+        assert!(next_batch(&mut entries, 10, 7).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_export_verifies() {
+        let pool = testing::database("bundle_export_verifies").await;
+        crate::postgres::init(&pool).await.unwrap();
+        let mut conn = pool.acquire().await.unwrap();
+        let scheme = crate::postgres::hash_scheme(&mut *conn).await.unwrap();
+
+        let content = odb::write_content(b"a", scheme, &mut *conn).await.unwrap();
+        let mut data = RegisterData::new();
+        data.insert(
+            RegisterEntryKey::try_from("a".to_string()).unwrap(),
+            content,
+        );
+        let register = odb::write(&data, scheme, &mut *conn).await.unwrap();
+        let refs = [Ref {
+            name: "refs/heads/main".into(),
+            oid: register,
+        }];
+
+        super::export(&mut conn, &refs, &[], &mut Contents, Vec::new())
+            .await
+            .unwrap();
+
+        sqlx::query("UPDATE braid.object SET encoded = encoded || '\\x00'::bytea WHERE id = $1")
+            .bind(register)
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        let result = super::export(&mut conn, &refs, &[], &mut Contents, Vec::new()).await;
+        assert!(
+            matches!(result, Err(Error::HashMismatch { expected, .. }) if expected == register)
+        );
+    }
+}
@@ -1,12 +1,24 @@
-use crate::Error;
+use braid_hash::Oid;
+use sqlx::postgres::PgDatabaseError;
 
-const ERR_DUPLICATE_SCHEMA: &'static str = "42P06";
+use crate::{Error, ObjectKind};
+
+const ERR_DUPLICATE_SCHEMA: &str = "42P06";
+const ERR_INVALID_SCHEMA_NAME: &str = "3F000";
+const ERR_UNDEFINED_TABLE: &str = "42P01";
+const ERR_FOREIGN_KEY_VIOLATION: &str = "23503";
+const ERR_UNIQUE_VIOLATION: &str = "23505";
+const ERR_SERIALIZATION_FAILURE: &str = "40001";
+const ERR_DEADLOCK_DETECTED: &str = "40P01";
+
+/// Raised by `braid.create_object`, see `sql/object-encoding.sql`.
+const ERR_OBJECT_KIND_CONFLICT: &str = "BR001";
 
 impl From<sqlx::Error> for Error {
     fn from(err: sqlx::Error) -> Self {
         if let Some(err) = err.as_database_error() {
-            if let Some(code) = err.code() {
-                if let Some(err) = map_postgres_error_code(code.as_ref()) {
+            if let Some(err) = err.try_downcast_ref::<PgDatabaseError>() {
+                if let Some(err) = map_postgres_error(err) {
                     return err;
                 }
             }
@@ -15,10 +27,117 @@ impl From<sqlx::Error> for Error {
     }
 }
 
-fn map_postgres_error_code(code: &str) -> Option<crate::Error> {
+impl Error {
+    /// Whether the failed transaction can be retried as is.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::SerializationFailure | Self::Deadlock)
+    }
+}
+
+fn map_postgres_error(err: &PgDatabaseError) -> Option<Error> {
     use crate::Error::*;
-    match code {
+    match err.code() {
         ERR_DUPLICATE_SCHEMA => Some(PostgresBackendAlreadyInitialized),
+        ERR_INVALID_SCHEMA_NAME | ERR_UNDEFINED_TABLE => Some(PostgresBackendNotInitialized),
+        ERR_SERIALIZATION_FAILURE => Some(SerializationFailure),
+        ERR_DEADLOCK_DETECTED => Some(Deadlock),
+        ERR_FOREIGN_KEY_VIOLATION => {
+            let detail = err.detail()?;
+            Some(MissingReference {
+                oid: parse_key_oid(detail)?,
+                kind: parse_referenced_kind(detail),
+            })
+        }
+        ERR_UNIQUE_VIOLATION => Some(UniqueViolation {
+            constraint: err.constraint()?.to_string(),
+            oid: err.detail().and_then(parse_key_oid),
+        }),
+        ERR_OBJECT_KIND_CONFLICT => {
+            let mut parts = err.detail()?.split(':');
+            let oid = Oid::try_from_str(parts.next()?).ok()?;
+            let existing = crate::ObjectKind::from_pg_str(parts.next()?)?;
+            let requested = crate::ObjectKind::from_pg_str(parts.next()?)?;
+            Some(ObjectKindConflict {
+                oid,
+                existing,
+                requested,
+            })
+        }
         _ => None,
     }
 }
+
+/// Parses the oid out of a detail such as
+/// `Key (parent)=(\x0c2e...) is not present in table "commit".`
+fn parse_key_oid(detail: &str) -> Option<Oid> {
+    const PREFIX: &str = "=(\\x";
+
+    let start = detail.find(PREFIX)? + PREFIX.len();
+    let hex = detail.get(start..start + Oid::LEN * 2)?;
+    Oid::try_from_str(hex).ok()
+}
+
+fn parse_referenced_kind(detail: &str) -> Option<ObjectKind> {
+    const PREFIX: &str = "in table \"";
+
+    let start = detail.find(PREFIX)? + PREFIX.len();
+    let end = start + detail[start..].find('"')?;
+    match &detail[start..end] {
+        // `object` and `save_parent` hold more than one kind
+        "object" | "save_parent" => None,
+        table => ObjectKind::from_pg_str(table),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use braid_hash::Oid;
+
+    use crate::{commit::Commit, postgres::testing, Error, ObjectKind};
+
+    #[test]
+    fn test_parse_foreign_key_detail() {
+        let detail = format!(
+            "Key (parent)=(\\x{}) is not present in table \"commit\".",
+            Commit::ROOT_ID
+        );
+
+        assert_eq!(super::parse_key_oid(&detail), Some(Commit::ROOT_ID));
+        assert_eq!(
+            super::parse_referenced_kind(&detail),
+            Some(ObjectKind::Commit)
+        );
+
+        let detail = format!(
+            "Key (content)=(\\x{}) is not present in table \"object\".",
+            Oid::ZERO
+        );
+
+        assert_eq!(super::parse_key_oid(&detail), Some(Oid::ZERO));
+        assert_eq!(super::parse_referenced_kind(&detail), None);
+    }
+
+    #[tokio::test]
+    async fn test_deadlock() {
+        let pool = testing::database("err_deadlock").await;
+        let mut first = pool.begin().await.unwrap();
+        let mut second = pool.begin().await.unwrap();
+
+        let lock = "SELECT pg_advisory_xact_lock($1)";
+        for (tran, key) in [(&mut first, 1), (&mut second, 2)] {
+            sqlx::query(lock)
+                .bind(key)
+                .execute(&mut **tran)
+                .await
+                .unwrap();
+        }
+
+        let (a, b) = tokio::join!(
+            sqlx::query(lock).bind(2).execute(&mut *first),
+            sqlx::query(lock).bind(1).execute(&mut *second),
+        );
+        let err = Error::from(a.err().or(b.err()).unwrap());
+        assert!(matches!(err, Error::Deadlock));
+        assert!(err.is_retryable());
+    }
+}
@@ -0,0 +1,353 @@
+use std::{collections::HashMap, io::Write};
+
+use braid_hash::{HashScheme, Oid};
+use sqlx::PgConnection;
+
+use crate::{
+    bundle::{ContentSource, Ref},
+    commit::CommitData,
+    fast_export::{FastExportWriter, FileChange, Mark},
+    register::RegisterData,
+    Error, Object, ObjectData, ObjectKind, Result,
+};
+
+use super::odb;
+
+/// Writes the history of `refs` as a `git fast-import` stream.
+///
+/// Commits are written parents first, each with the changes to the files of
+/// its first parent. Content bytes are read from `content`. Refs must be full
+/// git ref names such as `refs/heads/main`. Every object is verified against
+/// its oid before it is exported.
+pub async fn export<W: Write>(
+    conn: &mut PgConnection,
+    refs: &[Ref],
+    content: &mut impl ContentSource,
+    out: W,
+) -> Result<W> {
+    let scheme = super::hash_scheme(&mut *conn).await?;
+    let mut exporter = Exporter {
+        scheme,
+        writer: FastExportWriter::new(out),
+        blobs: HashMap::new(),
+        commits: HashMap::new(),
+    };
+
+    for r in refs {
+        let mut stack = vec![Visit::Enter(r.oid)];
+        while let Some(visit) = stack.pop() {
+            match visit {
+                Visit::Enter(oid) => {
+                    if exporter.commits.contains_key(&oid) {
+                        continue;
+                    }
+
+                    // the root commit has no files and no git counterpart
+                    let data = read_commit(oid, scheme, conn).await?;
+                    if data.parent().is_none() {
+                        continue;
+                    }
+                    let parents = [data.merge_parent(), data.parent()];
+                    stack.push(Visit::Leave(oid, Box::new(data)));
+                    stack.extend(parents.into_iter().flatten().map(Visit::Enter));
+                }
+                Visit::Leave(oid, data) => {
+                    exporter.commit(oid, &r.name, &data, content, conn).await?
+                }
+            }
+        }
+
+        if let Some((mark, _)) = exporter.commits.get(&r.oid) {
+            exporter.writer.reset(&r.name, *mark)?;
+        }
+    }
+
+    exporter.writer.finish()
+}
+
+enum Visit {
+    Enter(Oid),
+    Leave(Oid, Box<CommitData>),
+}
+
+enum Node {
+    Content,
+    Register(RegisterData<String>),
+}
+
+struct Exporter<W: Write> {
+    scheme: HashScheme,
+    writer: FastExportWriter<W>,
+    blobs: HashMap<Oid, Mark>,
+    /// The mark and register of every written commit.
+    commits: HashMap<Oid, (Mark, Oid)>,
+}
+
+impl<W: Write> Exporter<W> {
+    async fn commit(
+        &mut self,
+        oid: Oid,
+        refname: &str,
+        data: &CommitData,
+        content: &mut impl ContentSource,
+        conn: &mut PgConnection,
+    ) -> Result<()> {
+        let parent = data.parent().and_then(|parent| self.commits.get(&parent));
+        let from = parent.map(|(mark, _)| *mark);
+        let base = parent.map(|(_, register)| *register);
+        let merge = data
+            .merge_parent()
+            .and_then(|merge| self.commits.get(&merge))
+            .map(|(mark, _)| *mark);
+
+        let changes = self.diff(base, data.register(), content, conn).await?;
+        let mark = self.writer.commit(refname, data, from, merge, &changes)?;
+        self.commits.insert(oid, (mark, data.register()));
+        Ok(())
+    }
+
+    /// Lists the changes from register `old`, or no files, to `new`, writing
+    /// any new blobs.
+    async fn diff(
+        &mut self,
+        old: Option<Oid>,
+        new: Oid,
+        content: &mut impl ContentSource,
+        conn: &mut PgConnection,
+    ) -> Result<Vec<FileChange>> {
+        let mut changes = Vec::new();
+        if old == Some(new) {
+            return Ok(changes);
+        }
+
+        let old = match old {
+            Some(old) => read_register(old, self.scheme, conn).await?,
+            None => RegisterData::new(),
+        };
+        let mut stack = vec![(
+            String::new(),
+            old,
+            read_register(new, self.scheme, conn).await?,
+        )];
+
+        while let Some((prefix, old, new)) = stack.pop() {
+            let path = |key: &str| match prefix.is_empty() {
+                true => key.to_string(),
+                false => format!("{prefix}/{key}"),
+            };
+
+            for (key, _) in old.iter() {
+                if new.get(key.as_str()).is_none() {
+                    changes.push(FileChange::Delete { path: path(key) });
+                }
+            }
+
+            for (key, oid) in new.iter() {
+                let previous = old.get(key.as_str());
+                if previous == Some(oid) {
+                    continue;
+                }
+
+                let previous = match previous {
+                    Some(previous) => Some(load(*previous, self.scheme, conn).await?),
+                    None => None,
+                };
+
+                match (previous, load(*oid, self.scheme, conn).await?) {
+                    (Some(Node::Register(previous)), Node::Register(register)) => {
+                        stack.push((path(key), previous, register))
+                    }
+                    (previous, Node::Register(register)) => {
+                        if previous.is_some() {
+                            changes.push(FileChange::Delete { path: path(key) });
+                        }
+                        stack.push((path(key), RegisterData::new(), register));
+                    }
+                    (previous, Node::Content) => {
+                        if let Some(Node::Register(_)) = previous {
+                            changes.push(FileChange::Delete { path: path(key) });
+                        }
+                        let blob = self.blob(*oid, content)?;
+                        changes.push(FileChange::Modify {
+                            path: path(key),
+                            blob,
+                        });
+                    }
+                }
+            }
+        }
+
+        Ok(changes)
+    }
+
+    fn blob(&mut self, oid: Oid, content: &mut impl ContentSource) -> Result<Mark> {
+        if let Some(mark) = self.blobs.get(&oid) {
+            return Ok(*mark);
+        }
+
+        let mark = self.writer.blob(&content.read_content(oid)?)?;
+        self.blobs.insert(oid, mark);
+        Ok(mark)
+    }
+}
+
+async fn read_commit(oid: Oid, scheme: HashScheme, conn: &mut PgConnection) -> Result<CommitData> {
+    match load_data(oid, scheme, conn).await? {
+        Some(ObjectData::Commit(data)) => Ok(data),
+        Some(data) => Err(Error::UnexpectedKind {
+            expected: ObjectKind::Commit,
+            actual: data.kind(),
+        }),
+        None => Err(Error::UnexpectedKind {
+            expected: ObjectKind::Commit,
+            actual: ObjectKind::Content,
+        }),
+    }
+}
+
+async fn read_register(
+    oid: Oid,
+    scheme: HashScheme,
+    conn: &mut PgConnection,
+) -> Result<RegisterData<String>> {
+    match load(oid, scheme, conn).await? {
+        Node::Register(register) => Ok(register),
+        Node::Content => Err(Error::UnexpectedKind {
+            expected: ObjectKind::Register,
+            actual: ObjectKind::Content,
+        }),
+    }
+}
+
+/// Loads a register entry, which is either content or another register.
+async fn load(oid: Oid, scheme: HashScheme, conn: &mut PgConnection) -> Result<Node> {
+    match load_data(oid, scheme, conn).await? {
+        None => Ok(Node::Content),
+        Some(ObjectData::Register(register)) => Ok(Node::Register(register)),
+        Some(data) => Err(Error::UnexpectedKind {
+            expected: ObjectKind::Register,
+            actual: data.kind(),
+        }),
+    }
+}
+
+/// Decodes an object from its verified canonical encoding, `None` for content.
+async fn load_data(
+    oid: Oid,
+    scheme: HashScheme,
+    conn: &mut PgConnection,
+) -> Result<Option<ObjectData>> {
+    match odb::verified::read_raw(oid, scheme, conn).await {
+        Ok(Some((_, encoded))) => Ok(Some(Object::decode(&encoded, scheme)?.1)),
+        Ok(None) => Err(Error::MissingReference { oid, kind: None }),
+        Err(Error::UnencodedKind(ObjectKind::Content)) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use braid_hash::Oid;
+
+    use crate::{
+        bundle::{ContentSource, Ref},
+        bytes::Hash,
+        commit::{CommitData, Identity},
+        postgres::{odb, testing},
+        register::RegisterData,
+        RegisterEntryKey,
+    };
+
+    struct Contents(HashMap<Oid, Vec<u8>>);
+
+    impl ContentSource for Contents {
+        fn read_content(&mut self, oid: Oid) -> std::io::Result<Vec<u8>> {
+            Ok(self.0[&oid].clone())
+        }
+    }
+
+    fn register(entries: &[(&str, Oid)]) -> RegisterData<String> {
+        let mut data = RegisterData::new();
+        for (key, oid) in entries {
+            data.insert(RegisterEntryKey::try_from(key.to_string()).unwrap(), *oid);
+        }
+        data
+    }
+
+    #[tokio::test]
+    async fn test_export() {
+        let pool = testing::database("fast_export").await;
+        crate::postgres::init(&pool).await.unwrap();
+        let mut conn = pool.acquire().await.unwrap();
+        let scheme = crate::postgres::hash_scheme(&mut *conn).await.unwrap();
+
+        let mut contents = Contents(HashMap::new());
+        for bytes in [b"hello".as_slice(), b"bye"] {
+            let oid = odb::write_content(bytes, scheme, &mut *conn).await.unwrap();
+            contents.0.insert(oid, bytes.to_vec());
+        }
+        let hello = crate::content_oid(scheme, b"hello");
+        let bye = crate::content_oid(scheme, b"bye");
+
+        let root = CommitData::root(scheme).unwrap();
+        let (root_id, _) = root.hash_with(scheme).unwrap();
+        let date = time::OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+
+        let first = odb::write(&register(&[("a", hello)]), scheme, &mut *conn)
+            .await
+            .unwrap();
+        let first = CommitData::new(
+            first,
+            root_id,
+            None,
+            None,
+            root.saves(),
+            date,
+            Identity::new("Ada <ada@example.com>", ""),
+            "first",
+            "",
+        );
+        let first = odb::write(&first, scheme, &mut *conn).await.unwrap();
+
+        let dir = odb::write(&register(&[("x", bye)]), scheme, &mut *conn)
+            .await
+            .unwrap();
+        let second = register(&[("a", hello), ("d", dir)]);
+        let second = odb::write(&second, scheme, &mut *conn).await.unwrap();
+        let second = CommitData::new(
+            second,
+            first,
+            None,
+            None,
+            root.saves(),
+            date,
+            Identity::new("Bob\n<x>", "bob@example.com"),
+            "second",
+            "body",
+        );
+        let second = odb::write(&second, scheme, &mut *conn).await.unwrap();
+
+        let refs = [Ref {
+            name: "refs/heads/main".to_string(),
+            oid: second,
+        }];
+        let stream = super::export(&mut conn, &refs, &mut contents, Vec::new())
+            .await
+            .unwrap();
+
+        let expected = "blob\nmark :1\ndata 5\nhello\n\
+            commit refs/heads/main\nmark :2\n\
+            author Ada <ada@example.com> 1700000000 +0000\n\
+            committer Ada <ada@example.com> 1700000000 +0000\n\
+            data 6\nfirst\n\nM 100644 :1 a\n\n\
+            blob\nmark :3\ndata 3\nbye\n\
+            commit refs/heads/main\nmark :4\n\
+            author Bob x <bob@example.com> 1700000000 +0000\n\
+            committer Bob x <bob@example.com> 1700000000 +0000\n\
+            data 13\nsecond\n\nbody\n\nfrom :2\nM 100644 :3 d/x\n\n\
+            reset refs/heads/main\nfrom :4\n\n";
+        assert_eq!(String::from_utf8(stream).unwrap(), expected);
+    }
+}
@@ -0,0 +1,443 @@
+use std::collections::{HashMap, HashSet};
+
+use braid_hash::{HashScheme, Oid};
+use sqlx::PgConnection;
+use time::{Duration, OffsetDateTime};
+
+use crate::{
+    bytes::Hash, commit::CommitData, register::EntryData, save::SaveData, Object, ObjectData,
+    ObjectKind, Result,
+};
+
+use super::odb;
+
+/// A single integrity problem found by [`verify`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Issue {
+    /// The object's canonical encoding does not hash to its id.
+    HashMismatch {
+        kind: ObjectKind,
+        expected: Oid,
+        actual: Oid,
+    },
+
+    /// The object is listed in `braid.object` but its data row is missing.
+    MissingData { kind: ObjectKind, oid: Oid },
+
+    /// The object has no canonical encoding stored.
+    MissingEncoding { kind: ObjectKind, oid: Oid },
+
+    /// The stored encoding does not decode to an object of the listed kind.
+    InvalidEncoding { kind: ObjectKind, oid: Oid },
+
+    /// The relational rows differ from the object's canonical encoding.
+    RowMismatch { kind: ObjectKind, oid: Oid },
+
+    /// `referrer` points to an object that does not exist.
+    Dangling {
+        referrer: Oid,
+        oid: Oid,
+        expected: &'static [ObjectKind],
+    },
+
+    /// `referrer` points to an object of the wrong kind.
+    UnexpectedKind {
+        referrer: Oid,
+        oid: Oid,
+        expected: &'static [ObjectKind],
+        actual: ObjectKind,
+    },
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    /// The number of objects that were checked.
+    pub objects: usize,
+    pub issues: Vec<Issue>,
+    /// Objects not reachable from the given roots, the root commit or the empty registers.
+    pub unreachable: Vec<Oid>,
+}
+
+impl Report {
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+const COMMIT: &[ObjectKind] = &[ObjectKind::Commit];
+const REGISTER: &[ObjectKind] = &[ObjectKind::Register];
+const SAVE_REGISTER: &[ObjectKind] = &[ObjectKind::SaveRegister];
+const SAVE: &[ObjectKind] = &[ObjectKind::Save];
+const SAVE_PARENT: &[ObjectKind] = &[ObjectKind::Save, ObjectKind::Commit];
+const CONTENT: &[ObjectKind] = &[ObjectKind::Content];
+const REGISTER_ENTRY: &[ObjectKind] = &[ObjectKind::Content, ObjectKind::Register];
+
+/// The number of objects read per query.
+const BATCH_SIZE: usize = 1_000;
+
+/// Verifies every object in the database.
+///
+/// The stored encoding of each object is hashed and decoded, its relational
+/// rows are checked against it, every reference is checked to exist with the
+/// expected kind, and everything not reachable from `roots` is reported
+/// as unreachable. Run this inside a repeatable read transaction to get a
+/// consistent snapshot.
+pub async fn verify(
+    conn: &mut PgConnection,
+    roots: impl IntoIterator<Item = Oid>,
+) -> Result<Report> {
+    let scheme = super::hash_scheme(&mut *conn).await?;
+    let objects: Vec<(Oid, ObjectKind)> = sqlx::query_as("SELECT id, kind FROM braid.object")
+        .fetch_all(&mut *conn)
+        .await?;
+
+    let kinds: HashMap<Oid, ObjectKind> = objects.iter().copied().collect();
+
+    let mut checker = Checker {
+        scheme,
+        kinds: &kinds,
+        edges: HashMap::with_capacity(kinds.len()),
+        report: Report {
+            objects: kinds.len(),
+            ..Default::default()
+        },
+    };
+
+    let mut by_kind: HashMap<ObjectKind, Vec<Oid>> = HashMap::new();
+    for (oid, kind) in objects {
+        by_kind.entry(kind).or_default().push(oid);
+    }
+
+    for (kind, oids) in by_kind {
+        for batch in oids.chunks(BATCH_SIZE) {
+            checker.check(kind, batch, conn).await?;
+        }
+    }
+
+    let Checker {
+        edges, mut report, ..
+    } = checker;
+
+    let root = CommitData::root(scheme)?;
+    let roots = roots
+        .into_iter()
+        .chain([root.hash_with(scheme)?.0, root.register(), root.saves()]);
+
+    let mut reachable = HashSet::with_capacity(kinds.len());
+    let mut stack: Vec<Oid> = roots.collect();
+
+    while let Some(oid) = stack.pop() {
+        if !reachable.insert(oid) {
+            continue;
+        }
+
+        if let Some(children) = edges.get(&oid) {
+            stack.extend(children.iter().copied());
+        }
+    }
+
+    report.unreachable = kinds
+        .keys()
+        .filter(|oid| !reachable.contains(*oid))
+        .copied()
+        .collect();
+
+    Ok(report)
+}
+
+struct Checker<'a> {
+    scheme: HashScheme,
+    kinds: &'a HashMap<Oid, ObjectKind>,
+    edges: HashMap<Oid, Vec<Oid>>,
+    report: Report,
+}
+
+impl Checker<'_> {
+    /// Checks a batch of objects of one kind, reading them in a single query.
+    async fn check(
+        &mut self,
+        kind: ObjectKind,
+        oids: &[Oid],
+        conn: &mut PgConnection,
+    ) -> Result<()> {
+        if kind == ObjectKind::Content {
+            return Ok(());
+        }
+
+        let encoded = self.check_encodings(kind, oids, conn).await?;
+        let mut found = HashSet::with_capacity(oids.len());
+
+        match kind {
+            ObjectKind::Content => unreachable!(),
+            ObjectKind::Commit => {
+                for commit in odb::get_commits(oids, &mut *conn).await? {
+                    let (oid, data) = (commit.id(), commit.data());
+                    found.insert(oid);
+                    if let Some(ObjectData::Commit(encoded)) = encoded.get(&oid) {
+                        let encoded = CommitData {
+                            author_date: as_stored(encoded.author_date),
+                            date: as_stored(encoded.date),
+                            ..encoded.clone()
+                        };
+                        self.check_rows(kind, oid, *data == encoded);
+                    }
+                    self.reference(oid, data.register(), REGISTER);
+                    self.reference(oid, data.saves(), SAVE_REGISTER);
+                    let parents = [data.parent(), data.merge_parent(), data.rebase_of()];
+                    for parent in parents.into_iter().flatten() {
+                        self.reference(oid, parent, COMMIT);
+                    }
+                }
+            }
+            ObjectKind::Save => {
+                for save in odb::get_saves(oids, &mut *conn).await? {
+                    let (oid, data) = (save.id(), save.data());
+                    found.insert(oid);
+                    if let Some(ObjectData::Save(encoded)) = encoded.get(&oid) {
+                        let encoded = SaveData {
+                            date: as_stored(encoded.date),
+                            ..encoded.clone()
+                        };
+                        self.check_rows(kind, oid, *data == encoded);
+                    }
+                    self.reference(oid, data.content(), CONTENT);
+                    self.reference(oid, data.parent(), SAVE_PARENT);
+                }
+            }
+            ObjectKind::Register => {
+                for register in odb::get_registers(oids, &mut *conn).await? {
+                    let (oid, data) = (register.id(), register.data());
+                    found.insert(oid);
+                    if let Some(ObjectData::Register(encoded)) = encoded.get(&oid) {
+                        self.check_rows(kind, oid, data == encoded);
+                    }
+                    self.check_entries(oid, data, REGISTER_ENTRY);
+                }
+            }
+            ObjectKind::SaveRegister => {
+                for register in odb::get_save_registers(oids, &mut *conn).await? {
+                    let (oid, data) = (register.id(), register.data());
+                    found.insert(oid);
+                    if let Some(ObjectData::SaveRegister(encoded)) = encoded.get(&oid) {
+                        self.check_rows(kind, oid, data == encoded);
+                    }
+                    self.check_entries(oid, data, SAVE);
+                }
+            }
+        }
+
+        for oid in oids.iter().filter(|oid| !found.contains(*oid)) {
+            self.report
+                .issues
+                .push(Issue::MissingData { kind, oid: *oid });
+        }
+
+        Ok(())
+    }
+
+    /// Hashes and decodes the stored encodings of a batch, returning the
+    /// objects whose encoding is authentic.
+    async fn check_encodings(
+        &mut self,
+        kind: ObjectKind,
+        oids: &[Oid],
+        conn: &mut PgConnection,
+    ) -> Result<HashMap<Oid, ObjectData>> {
+        let rows: Vec<(Oid, Option<Vec<u8>>)> =
+            sqlx::query_as("SELECT id, encoded FROM braid.object WHERE id = ANY($1)")
+                .bind(oids)
+                .fetch_all(&mut *conn)
+                .await?;
+
+        let mut objects = HashMap::with_capacity(rows.len());
+        for (oid, encoded) in rows {
+            let Some(encoded) = encoded else {
+                self.report
+                    .issues
+                    .push(Issue::MissingEncoding { kind, oid });
+                continue;
+            };
+
+            let actual = self.scheme.hash(kind.domain(), &encoded);
+            if actual != oid {
+                self.report.issues.push(Issue::HashMismatch {
+                    kind,
+                    expected: oid,
+                    actual,
+                });
+                continue;
+            }
+
+            match Object::decode(&encoded, self.scheme) {
+                Ok((_, data)) if data.kind() == kind => {
+                    objects.insert(oid, data);
+                }
+                _ => self
+                    .report
+                    .issues
+                    .push(Issue::InvalidEncoding { kind, oid }),
+            }
+        }
+        Ok(objects)
+    }
+
+    fn check_rows(&mut self, kind: ObjectKind, oid: Oid, matches: bool) {
+        if !matches {
+            self.report.issues.push(Issue::RowMismatch { kind, oid });
+        }
+    }
+
+    fn check_entries<R: EntryData<String>>(
+        &mut self,
+        oid: Oid,
+        data: &R,
+        expected: &'static [ObjectKind],
+    ) {
+        for (_, entry) in data.iter() {
+            self.reference(oid, *entry, expected);
+        }
+    }
+
+    fn reference(&mut self, referrer: Oid, oid: Oid, expected: &'static [ObjectKind]) {
+        self.edges.entry(referrer).or_default().push(oid);
+
+        match self.kinds.get(&oid) {
+            None => self.report.issues.push(Issue::Dangling {
+                referrer,
+                oid,
+                expected,
+            }),
+            Some(actual) if !expected.contains(actual) => {
+                self.report.issues.push(Issue::UnexpectedKind {
+                    referrer,
+                    oid,
+                    expected,
+                    actual: *actual,
+                })
+            }
+            Some(_) => {}
+        }
+    }
+}
+
+/// A date as a `timestamptz` column keeps it: whole microseconds since the
+/// Postgres epoch, truncated the way sqlx encodes it.
+fn as_stored(date: OffsetDateTime) -> OffsetDateTime {
+    let epoch = OffsetDateTime::UNIX_EPOCH + Duration::seconds(946_684_800);
+    let micros = (date - epoch).whole_microseconds();
+    epoch + Duration::microseconds(micros as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use braid_hash::Oid;
+    use time::{OffsetDateTime, UtcOffset};
+
+    use super::Issue;
+    use crate::{
+        bytes::Hash,
+        commit::{CommitData, Identity},
+        postgres::{odb, testing},
+        register::RegisterData,
+        save::SaveData,
+        ObjectKind, RegisterEntryKey,
+    };
+
+    #[tokio::test]
+    async fn test_verify() {
+        let pool = testing::database("fsck").await;
+        crate::postgres::init(&pool).await.unwrap();
+        let mut conn = pool.acquire().await.unwrap();
+        let scheme = crate::postgres::hash_scheme(&mut *conn).await.unwrap();
+
+        let content = odb::write_content(b"hello", scheme, &mut *conn)
+            .await
+            .unwrap();
+        let mut registers = Vec::new();
+        for i in 0..3 {
+            let mut data = RegisterData::new();
+            data.insert(RegisterEntryKey::try_from(format!("{i}")).unwrap(), content);
+            registers.push(odb::write(&data, scheme, &mut *conn).await.unwrap());
+        }
+
+        let report = super::verify(&mut conn, registers.clone()).await.unwrap();
+        assert!(report.is_ok(), "{:?}", report.issues);
+        assert_eq!(report.unreachable, Vec::<Oid>::new());
+
+        sqlx::query("DELETE FROM braid.register_entry WHERE register = ANY($1)")
+            .bind(&registers[..2])
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        sqlx::query("DELETE FROM braid.register WHERE id = $1")
+            .bind(registers[1])
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+
+        let report = super::verify(&mut conn, registers.clone()).await.unwrap();
+        assert_eq!(report.issues.len(), 2, "{:?}", report.issues);
+        assert!(report.issues.contains(&Issue::RowMismatch {
+            kind: ObjectKind::Register,
+            oid: registers[0],
+        }));
+        assert!(report.issues.contains(&Issue::MissingData {
+            kind: ObjectKind::Register,
+            oid: registers[1],
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_encodings() {
+        let pool = testing::database("fsck_encodings").await;
+        crate::postgres::init(&pool).await.unwrap();
+        let mut conn = pool.acquire().await.unwrap();
+        let scheme = crate::postgres::hash_scheme(&mut *conn).await.unwrap();
+
+        let date = OffsetDateTime::from_unix_timestamp_nanos(1_700_000_000_123_456_789)
+            .unwrap()
+            .to_offset(UtcOffset::from_hms(1, 0, 0).unwrap());
+        let root = CommitData::root(scheme).unwrap();
+        let (parent, _) = root.hash_with(scheme).unwrap();
+        let commit = CommitData::new(
+            root.register(),
+            parent,
+            None,
+            None,
+            root.saves(),
+            date,
+            Identity::new("alice", "alice@example.com"),
+            "summary",
+            "",
+        );
+        let commit = odb::write(&commit, scheme, &mut *conn).await.unwrap();
+        let content = odb::write_content(b"a", scheme, &mut *conn).await.unwrap();
+        let save = SaveData::new("alice", date, content, commit);
+        let save = odb::write(&save, scheme, &mut *conn).await.unwrap();
+
+        let report = super::verify(&mut conn, [commit, save]).await.unwrap();
+        assert!(report.is_ok(), "{:?}", report.issues);
+
+        sqlx::query("UPDATE braid.commit SET summary = 'tampered' WHERE id = $1")
+            .bind(commit)
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        sqlx::query("UPDATE braid.object SET encoded = encoded || '\\x00'::bytea WHERE id = $1")
+            .bind(save)
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+
+        let report = super::verify(&mut conn, [commit, save]).await.unwrap();
+        assert_eq!(report.issues.len(), 2, "{:?}", report.issues);
+        assert!(report.issues.contains(&Issue::RowMismatch {
+            kind: ObjectKind::Commit,
+            oid: commit,
+        }));
+        assert!(report.issues.iter().any(|issue| matches!(
+            issue,
+            Issue::HashMismatch { kind: ObjectKind::Save, expected, .. } if *expected == save
+        )));
+    }
+}
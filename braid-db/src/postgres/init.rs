@@ -1,35 +1,82 @@
+use braid_hash::HashScheme;
 use sqlx::PgPool;
 
 use crate::{
-    commit::Commit, register::{Register, SaveRegister}, Result
+    commit::CommitData,
+    register::{RegisterData, SaveRegisterData},
+    Error, Result,
 };
 
-pub(super) async fn init(pool: &PgPool) -> Result<()> {
+pub(super) async fn init(pool: &PgPool, scheme: HashScheme) -> Result<()> {
     let mut tran = pool.begin().await?;
+    super::migrate::migrate(&mut tran).await?;
 
-    const INIT: &[&str] = &[
-        "CREATE SCHEMA braid;",
-        include_str!("sql/init-braid.sql"),
-        "CALL braid.init_braid();",
-        "DROP PROCEDURE braid.init_braid;",
-    ];
-
-    for sql in INIT {
-        sqlx::query(sql).execute(&mut *tran).await?;
-    }
-
-    sqlx::query("CALL braid.create_register($1, ARRAY[]::braid.entry_records)")
-        .bind(Register::EMPTY_ID)
-        .execute(&mut *tran)
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT FROM braid.repository)")
+        .fetch_one(&mut *tran)
         .await?;
+    if exists {
+        return Err(Error::PostgresBackendAlreadyInitialized);
+    }
 
-    sqlx::query("CALL braid.create_save_register($1, ARRAY[]::braid.entry_records)")
-        .bind(SaveRegister::EMPTY_ID)
+    sqlx::query("INSERT INTO braid.repository (hash_algorithm, hash_domains) VALUES ($1, $2)")
+        .bind(scheme.algorithm().as_str())
+        .bind(scheme.domains())
         .execute(&mut *tran)
         .await?;
 
-    super::odb::write(&Commit::ROOT.data, &mut *tran).await?;
+    // the empty registers and the root commit every repository starts with
+    super::odb::write(&RegisterData::<String>::new(), scheme, &mut *tran).await?;
+    super::odb::write(&SaveRegisterData::<String>::new(), scheme, &mut *tran).await?;
+    super::odb::write(&CommitData::root(scheme)?, scheme, &mut *tran).await?;
 
     tran.commit().await?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use braid_hash::{HashAlgorithm, HashScheme};
+
+    use crate::{
+        oid::{CommitOid, RegisterOid, SaveRegisterOid, ValidOid},
+        postgres::{odb, testing},
+        Error,
+    };
+
+    #[tokio::test]
+    async fn test_init() {
+        let plain = HashScheme::new(HashAlgorithm::Blake3);
+        for (name, scheme) in [
+            ("init_plain", plain),
+            ("init_domains", plain.with_domains()),
+        ] {
+            let pool = testing::database(name).await;
+            crate::postgres::init_with(&pool, scheme).await.unwrap();
+            let mut conn = pool.acquire().await.unwrap();
+            assert_eq!(
+                crate::postgres::hash_scheme(&mut *conn).await.unwrap(),
+                scheme
+            );
+
+            let root = CommitOid::root(scheme).unwrap();
+            let validated = odb::validate::<CommitOid>(root.oid(), &mut *conn).await;
+            assert_eq!(validated.unwrap(), root);
+            let saves = SaveRegisterOid::empty(scheme).unwrap();
+            let validated = odb::validate::<SaveRegisterOid>(saves.oid(), &mut *conn).await;
+            assert_eq!(validated.unwrap(), saves);
+
+            // the empty register is read from the database like any other
+            let empty = RegisterOid::empty(scheme).unwrap();
+            let register = odb::verified::get_register(empty.oid(), scheme, &mut *conn)
+                .await
+                .unwrap()
+                .unwrap();
+            assert!(register.data().is_empty());
+
+            assert!(matches!(
+                crate::postgres::init_with(&pool, scheme).await,
+                Err(Error::PostgresBackendAlreadyInitialized)
+            ));
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use sqlx::{
+    postgres::{PgTypeInfo, PgValueRef},
+    Decode, Encode, Postgres, Type, TypeInfo,
+};
+
+use crate::ObjectKind;
+
+impl ObjectKind {
+    pub(super) const fn as_pg_str(self) -> &'static str {
+        match self {
+            Self::Register => "register",
+            Self::Commit => "commit",
+            Self::Save => "save",
+            Self::SaveRegister => "save_register",
+            Self::Content => "content",
+        }
+    }
+
+    pub(super) fn from_pg_str(s: &str) -> Option<Self> {
+        Some(match s {
+            "register" => Self::Register,
+            "commit" => Self::Commit,
+            "save" => Self::Save,
+            "save_register" => Self::SaveRegister,
+            "content" => Self::Content,
+            _ => return None,
+        })
+    }
+}
+
+impl Type<Postgres> for ObjectKind {
+    fn type_info() -> PgTypeInfo {
+        PgTypeInfo::with_name("braid.object_kind")
+    }
+
+    fn compatible(ty: &PgTypeInfo) -> bool {
+        // enums are reported without their schema when decoding
+        matches!(ty.name(), "braid.object_kind" | "object_kind")
+    }
+}
+
+impl Encode<'_, Postgres> for ObjectKind {
+    fn encode_by_ref(
+        &self,
+        buf: &mut <Postgres as sqlx::database::HasArguments<'_>>::ArgumentBuffer,
+    ) -> sqlx::encode::IsNull {
+        <&str as Encode<Postgres>>::encode_by_ref(&self.as_pg_str(), buf)
+    }
+}
+
+impl Decode<'_, Postgres> for ObjectKind {
+    fn decode(value: PgValueRef<'_>) -> Result<Self, sqlx::error::BoxDynError> {
+        let s = <&str as Decode<Postgres>>::decode(value)?;
+        Self::from_pg_str(s).ok_or_else(|| format!("Unknown braid.object_kind: {s:?}").into())
+    }
+}
@@ -0,0 +1,375 @@
+use braid_hash::Oid;
+use sqlx::PgConnection;
+
+use crate::{Error, Result};
+
+struct Migration {
+    version: i32,
+    name: &'static str,
+    sql: &'static str,
+    repair: Option<Repair>,
+}
+
+/// Runs after the SQL of every pending migration, so it works on the current
+/// schema, see [`super::repair`]. Returns the objects it could not repair.
+#[derive(Clone, Copy)]
+enum Repair {
+    ObjectEncodings,
+    RegisterEntries,
+}
+
+impl Repair {
+    async fn run(self, conn: &mut PgConnection) -> Result<Vec<Oid>> {
+        match self {
+            Self::ObjectEncodings => super::repair::object_encodings(conn).await,
+            Self::RegisterEntries => super::repair::register_entries(conn).await,
+        }
+    }
+}
+
+/// What [`migrate`](super::migrate) did.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationReport {
+    /// The schema version before, 0 for a new database.
+    pub from: i32,
+    /// Objects whose data was lost before the migrations and can't be
+    /// re-derived, such as registers missing entries they shared with an
+    /// older register. They fail to verify and need to be written again.
+    pub unrepairable: Vec<Oid>,
+}
+
+/// Every migration in order, version `n` is reached by applying the first `n`.
+/// Applied migrations must never change, fixes go in a new migration.
+const MIGRATIONS: &[Migration] = &[
+    // the schema created before migrations existed, which must never change
+    Migration {
+        version: 1,
+        name: "init-braid",
+        sql: concat!(
+            include_str!("sql/init-braid.sql"),
+            "\nCALL braid.init_braid();",
+            "\nDROP PROCEDURE braid.init_braid;",
+        ),
+        repair: None,
+    },
+    Migration {
+        version: 2,
+        name: "create-object-error",
+        sql: include_str!("sql/create-object-error.sql"),
+        repair: None,
+    },
+    Migration {
+        version: 3,
+        name: "object-encoding",
+        sql: include_str!("sql/object-encoding.sql"),
+        repair: Some(Repair::ObjectEncodings),
+    },
+    Migration {
+        version: 4,
+        name: "signature",
+        sql: include_str!("sql/signature.sql"),
+        repair: None,
+    },
+    Migration {
+        version: 5,
+        name: "commit-identities",
+        sql: include_str!("sql/commit-identities.sql"),
+        repair: None,
+    },
+    Migration {
+        version: 6,
+        name: "commit-headers",
+        sql: include_str!("sql/commit-headers.sql"),
+        repair: None,
+    },
+    Migration {
+        version: 7,
+        name: "repository",
+        sql: include_str!("sql/repository.sql"),
+        repair: None,
+    },
+    Migration {
+        version: 8,
+        name: "empty-registers",
+        sql: include_str!("sql/empty-registers.sql"),
+        repair: None,
+    },
+    Migration {
+        version: 9,
+        name: "hash-domains",
+        sql: include_str!("sql/hash-domains.sql"),
+        repair: None,
+    },
+    Migration {
+        version: 10,
+        name: "register-entry-key",
+        sql: include_str!("sql/register-entry-key.sql"),
+        repair: Some(Repair::RegisterEntries),
+    },
+    Migration {
+        version: 11,
+        name: "content-stream-root",
+        sql: include_str!("sql/content-stream-root.sql"),
+        repair: None,
+    },
+];
+
+/// The schema version this library reads and writes.
+pub const SCHEMA_VERSION: i32 = MIGRATIONS.len() as i32;
+
+/// Serializes migrations of the same database, see `pg_advisory_xact_lock`.
+const LOCK_KEY: i64 = i64::from_be_bytes(*b"\0\0\0braid");
+
+/// Applies pending migrations within the caller's transaction.
+pub(super) async fn migrate(conn: &mut PgConnection) -> Result<MigrationReport> {
+    migrate_to(conn, SCHEMA_VERSION).await
+}
+
+async fn migrate_to(conn: &mut PgConnection, target: i32) -> Result<MigrationReport> {
+    sqlx::query("SELECT pg_advisory_xact_lock($1)")
+        .bind(LOCK_KEY)
+        .execute(&mut *conn)
+        .await?;
+
+    let version = current_version(&mut *conn).await?;
+    if version > SCHEMA_VERSION {
+        return Err(Error::SchemaTooNew {
+            version,
+            supported: SCHEMA_VERSION,
+        });
+    }
+
+    let pending = &MIGRATIONS[(version.min(target) as usize)..target as usize];
+    for migration in pending {
+        sqlx::raw_sql(migration.sql).execute(&mut *conn).await?;
+        sqlx::query("INSERT INTO braid.schema_version (version, name) VALUES ($1, $2)")
+            .bind(migration.version)
+            .bind(migration.name)
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    let mut unrepairable = Vec::new();
+    for repair in pending.iter().filter_map(|migration| migration.repair) {
+        unrepairable.extend(repair.run(&mut *conn).await?);
+    }
+    // an object can be reported by several repairs
+    unrepairable.sort_by_key(|oid| oid.into_inner());
+    unrepairable.dedup();
+
+    Ok(MigrationReport {
+        from: version,
+        unrepairable,
+    })
+}
+
+async fn current_version(conn: &mut PgConnection) -> Result<i32> {
+    // schemas created before migrations existed are at the baseline version
+    let legacy: bool = sqlx::query_scalar(
+        "SELECT to_regclass('braid.object') IS NOT NULL
+            AND to_regclass('braid.schema_version') IS NULL",
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    sqlx::raw_sql(
+        "CREATE SCHEMA IF NOT EXISTS braid;
+        CREATE TABLE IF NOT EXISTS braid.schema_version (
+            version integer PRIMARY KEY,
+            name text NOT NULL,
+            applied_at timestamp with time zone NOT NULL DEFAULT now()
+        );",
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    if legacy {
+        let baseline = &MIGRATIONS[0];
+        sqlx::query("INSERT INTO braid.schema_version (version, name) VALUES ($1, $2)")
+            .bind(baseline.version)
+            .bind(baseline.name)
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    let version: Option<i32> = sqlx::query_scalar("SELECT max(version) FROM braid.schema_version")
+        .fetch_one(&mut *conn)
+        .await?;
+    Ok(version.unwrap_or(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use braid_hash::Oid;
+
+    use super::{MigrationReport, SCHEMA_VERSION};
+    use crate::{
+        bytes::Hash,
+        commit::{CommitData, Identity},
+        postgres::{
+            fsck, odb,
+            testing::{self, BASELINE_SCHEME},
+        },
+        register::RegisterData,
+        RegisterEntryKey,
+    };
+
+    #[test]
+    fn test_versions() {
+        for (i, migration) in super::MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version, i as i32 + 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_new_database() {
+        let pool = testing::database("migrate_new").await;
+
+        let report = crate::postgres::migrate(&pool).await.unwrap();
+        assert_eq!(report.from, 0);
+        assert!(report.unrepairable.is_empty());
+
+        crate::postgres::init(&pool).await.unwrap();
+        let report = crate::postgres::migrate(&pool).await.unwrap();
+        assert_eq!(report.from, SCHEMA_VERSION);
+    }
+
+    #[tokio::test]
+    async fn test_baseline() {
+        let pool = testing::baseline("migrate_baseline").await;
+        let mut conn = pool.acquire().await.unwrap();
+
+        let content = crate::content_oid(BASELINE_SCHEME, b"hello");
+        sqlx::query("CALL braid.create_content($1)")
+            .bind(content)
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+
+        let mut register = RegisterData::new();
+        register.insert(
+            RegisterEntryKey::try_from("hello".to_string()).unwrap(),
+            content,
+        );
+        let register = testing::baseline_register(&register, &mut conn).await;
+
+        let root = CommitData::root(BASELINE_SCHEME).unwrap();
+        let (root_id, _) = root.hash_with(BASELINE_SCHEME).unwrap();
+        let date = time::OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        let data = CommitData::new(
+            register,
+            root_id,
+            None,
+            None,
+            root.saves(),
+            date,
+            Identity::new("alice", ""),
+            "add hello",
+            "",
+        );
+        let commit = testing::baseline_commit(&data, &mut conn).await;
+
+        let report = crate::postgres::migrate(&pool).await.unwrap();
+        assert_eq!(
+            report,
+            MigrationReport {
+                from: 1,
+                unrepairable: Vec::new(),
+            }
+        );
+        let scheme = crate::postgres::open(&pool).await.unwrap();
+        assert_eq!(scheme, BASELINE_SCHEME);
+
+        let read = odb::verified::get_commit(commit, scheme, &mut *conn)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(read.data().committer().name(), "alice");
+        assert_eq!(read.data().author().name(), "alice");
+        assert_eq!(read.data().author_date(), date);
+
+        let report = fsck::verify(&mut conn, [commit]).await.unwrap();
+        assert!(report.is_ok(), "{:?}", report.issues);
+        assert_eq!(report.objects, 6);
+        assert!(report.unreachable.is_empty());
+
+        // encodings are complete, so new objects pass the check constraint
+        let child = CommitData::new(
+            register,
+            commit,
+            None,
+            None,
+            root.saves(),
+            date,
+            Identity::new("bob", "bob@example.com"),
+            "later",
+            "",
+        );
+        let child: Oid = odb::write(&child, scheme, &mut *conn).await.unwrap();
+        odb::verified::get_commit(child, scheme, &mut *conn)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let report = crate::postgres::migrate(&pool).await.unwrap();
+        assert_eq!(report.from, SCHEMA_VERSION);
+    }
+
+    #[tokio::test]
+    async fn test_register_entry_key() {
+        let pool = testing::database("migrate_register_entry_key").await;
+        let mut conn = pool.acquire().await.unwrap();
+        super::migrate_to(&mut conn, 9).await.unwrap();
+        let scheme = BASELINE_SCHEME;
+        sqlx::query(
+            "INSERT INTO braid.repository (hash_algorithm, hash_domains) VALUES ('blake3', FALSE)",
+        )
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+
+        let content = crate::content_oid(scheme, b"a");
+        sqlx::query("CALL braid.create_content($1)")
+            .bind(content)
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        let key = |key: &str| RegisterEntryKey::try_from(key.to_string()).unwrap();
+        let mut first = RegisterData::new();
+        first.insert(key("a"), content);
+        let first = odb::write(&first, scheme, &mut *conn).await.unwrap();
+        let mut second = RegisterData::new();
+        second.insert(key("a"), content);
+        second.insert(key("b"), content);
+        let second = odb::write(&second, scheme, &mut *conn).await.unwrap();
+
+        // keyed by (key, content), the second register lost its entry `a`
+        let rows = odb::get_register(second, &mut *conn)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(rows.data().len(), 1);
+
+        let report = super::migrate(&mut conn).await.unwrap();
+        assert_eq!(
+            report,
+            MigrationReport {
+                from: 9,
+                unrepairable: Vec::new(),
+            }
+        );
+        odb::verified::get_register(first, scheme, &mut *conn)
+            .await
+            .unwrap()
+            .unwrap();
+        odb::verified::get_register(second, scheme, &mut *conn)
+            .await
+            .unwrap()
+            .unwrap();
+        let rows = odb::get_register(second, &mut *conn)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(rows.data().len(), 2);
+    }
+}
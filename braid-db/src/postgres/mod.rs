@@ -1,17 +1,73 @@
+use braid_hash::HashScheme;
 use sqlx::{PgPool, Postgres};
 
-use crate::Result;
+use crate::{Error, Result};
 
 mod err;
 mod init;
+mod kind;
+mod migrate;
+mod repair;
+#[cfg(test)]
+pub(crate) mod testing;
 
+pub mod bundle;
+pub mod fast_export;
+pub mod fsck;
 pub mod odb;
-pub mod state;
+pub mod rehash;
+#[cfg(feature = "signing")]
+pub mod signature;
+
+pub use migrate::{MigrationReport, SCHEMA_VERSION};
 
-type Transaction<'a> = sqlx::Transaction<'a, Postgres>;
 pub trait Executor<'a>: sqlx::Executor<'a, Database = Postgres> {}
 impl<'a, T: sqlx::Executor<'a, Database = Postgres>> Executor<'a> for T {}
 
+/// Applies pending schema migrations in one transaction. Fails with
+/// [`Error::SchemaTooNew`](crate::Error::SchemaTooNew) if the database was
+/// migrated by a newer version of this library.
+pub async fn migrate(pool: &PgPool) -> Result<MigrationReport> {
+    let mut tran = pool.begin().await?;
+    let report = migrate::migrate(&mut tran).await?;
+    tran.commit().await?;
+    Ok(report)
+}
+
+/// Migrates the schema of a repository created with [`init`], returning its
+/// hash scheme. Call [`migrate`] first to learn about objects the migrations
+/// could not repair.
+pub async fn open(pool: &PgPool) -> Result<HashScheme> {
+    let mut tran = pool.begin().await?;
+    migrate::migrate(&mut tran).await?;
+    let scheme = hash_scheme(&mut *tran).await?;
+    tran.commit().await?;
+    Ok(scheme)
+}
+
+/// Creates a repository hashing with the default scheme.
 pub async fn init(pool: &PgPool) -> Result<()> {
-    init::init(pool).await
+    init::init(pool, HashScheme::default()).await
+}
+
+/// Creates a repository hashing with `scheme`, which can't be changed later.
+/// Use [`rehash::rehash`] to move existing objects to a new scheme.
+pub async fn init_with(pool: &PgPool, scheme: HashScheme) -> Result<()> {
+    init::init(pool, scheme).await
+}
+
+/// The hash scheme recorded for the repository.
+pub async fn hash_scheme(exec: impl Executor<'_>) -> Result<HashScheme> {
+    let (algorithm, domains): (String, bool) =
+        sqlx::query_as("SELECT hash_algorithm, hash_domains FROM braid.repository")
+            .fetch_optional(exec)
+            .await?
+            .ok_or(Error::PostgresBackendNotInitialized)?;
+
+    let scheme = HashScheme::new(algorithm.parse()?);
+    Ok(if domains {
+        scheme.with_domains()
+    } else {
+        scheme
+    })
 }
@@ -0,0 +1,744 @@
+//! Batched writes for imports and syncs of many objects.
+
+use std::collections::{hash_map, HashMap, HashSet};
+
+use braid_hash::{HashScheme, Oid};
+use sqlx::{Connection, PgConnection};
+use time::OffsetDateTime;
+
+use crate::{
+    bytes::Hash,
+    commit::CommitData,
+    register::{EntryData, RegisterData, SaveRegisterData},
+    save::SaveData,
+    Error, ObjectKind, Result,
+};
+
+/// The batch size of [`BulkWriter::new`].
+pub const DEFAULT_BATCH_SIZE: usize = 10_000;
+
+/// Accumulates objects of every kind and writes them with one multi-row
+/// insert per table, instead of a `CALL braid.create_*` per object.
+///
+/// Objects can be added in any order, as long as everything they reference is
+/// in the same batch or already written. Tables are filled in dependency order
+/// and objects that already exist are skipped.
+pub struct BulkWriter {
+    scheme: HashScheme,
+    batch_size: usize,
+    kinds: HashMap<Oid, ObjectKind>,
+    pending: Vec<Pending>,
+}
+
+struct Pending {
+    oid: Oid,
+    encoded: Option<Vec<u8>>,
+    row: Row,
+}
+
+enum Row {
+    /// Holds the stream root.
+    Content(Oid),
+    Register(Vec<(String, Oid)>),
+    SaveRegister(Vec<(String, Oid)>),
+    Save(SaveData<String>),
+    Commit(Box<CommitData<String>>),
+}
+
+impl Row {
+    fn kind(&self) -> ObjectKind {
+        match self {
+            Self::Content(_) => ObjectKind::Content,
+            Self::Register(_) => ObjectKind::Register,
+            Self::SaveRegister(_) => ObjectKind::SaveRegister,
+            Self::Save(_) => ObjectKind::Save,
+            Self::Commit(_) => ObjectKind::Commit,
+        }
+    }
+}
+
+impl BulkWriter {
+    /// Creates a writer hashing with `scheme`, which must be the repository's.
+    pub fn new(scheme: HashScheme) -> Self {
+        Self {
+            scheme,
+            batch_size: DEFAULT_BATCH_SIZE,
+            kinds: HashMap::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn with_batch_size(self, batch_size: usize) -> Self {
+        Self { batch_size, ..self }
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Whether the batch has reached its size and should be flushed.
+    pub fn is_full(&self) -> bool {
+        self.pending.len() >= self.batch_size
+    }
+
+    /// Adds an object to the batch and returns its oid.
+    pub fn add(&mut self, obj: &impl add::Add) -> Result<Oid> {
+        obj.add_to(self)
+    }
+
+    /// Adds content stored elsewhere by its bytes and returns its oid, see
+    /// [`write_content`](super::write_content).
+    pub fn add_content(&mut self, bytes: &[u8]) -> Result<Oid> {
+        let oid = crate::content_oid(self.scheme, bytes);
+        self.push(oid, None, Row::Content(braid_hash::hash(bytes)))?;
+        Ok(oid)
+    }
+
+    fn push(&mut self, oid: Oid, encoded: Option<Vec<u8>>, row: Row) -> Result<()> {
+        let requested = row.kind();
+        match self.kinds.entry(oid) {
+            hash_map::Entry::Occupied(entry) if *entry.get() != requested => {
+                Err(Error::ObjectKindConflict {
+                    oid,
+                    existing: *entry.get(),
+                    requested,
+                })
+            }
+            hash_map::Entry::Occupied(_) => Ok(()),
+            hash_map::Entry::Vacant(entry) => {
+                entry.insert(requested);
+                self.pending.push(Pending { oid, encoded, row });
+                Ok(())
+            }
+        }
+    }
+
+    /// Writes the batch in one transaction and returns how many objects were
+    /// new. The batch is kept if writing fails.
+    pub async fn flush(&mut self, conn: &mut PgConnection) -> Result<u64> {
+        if self.pending.is_empty() {
+            return Ok(0);
+        }
+
+        let mut tran = conn.begin().await?;
+        let ids: Vec<Oid> = self.kinds.keys().copied().collect();
+        let existing = self.existing(&ids, &mut tran).await?;
+
+        let mut columns = Columns::default();
+        for pending in &self.pending {
+            if !existing.contains(&pending.oid) {
+                columns.push(pending);
+            } else if let Row::Content(stream_root) = &pending.row {
+                // content recorded again gets the stream root it was missing
+                columns.content.0.push(pending.oid);
+                columns.content.1.push(*stream_root);
+            }
+        }
+
+        // objects another session wrote since must have the same kind too,
+        // like `braid.create_object` checks
+        let inserted = columns.insert_objects(&mut tran).await?;
+        let raced: Vec<Oid> = columns
+            .object
+            .id
+            .iter()
+            .filter(|oid| !inserted.contains(oid))
+            .copied()
+            .collect();
+        self.existing(&raced, &mut tran).await?;
+
+        columns.insert(&mut tran).await?;
+        tran.commit().await?;
+        let written = inserted.len() as u64;
+
+        self.kinds.clear();
+        self.pending.clear();
+        Ok(written)
+    }
+
+    /// Those of the pending `ids` that already exist, which must have the same
+    /// kind.
+    async fn existing(&self, ids: &[Oid], conn: &mut PgConnection) -> Result<HashSet<Oid>> {
+        if ids.is_empty() {
+            return Ok(HashSet::new());
+        }
+
+        let rows: Vec<(Oid, ObjectKind)> =
+            sqlx::query_as("SELECT id, kind FROM braid.object WHERE id = ANY($1)")
+                .bind(ids)
+                .fetch_all(conn)
+                .await?;
+
+        for &(oid, existing) in &rows {
+            let requested = self.kinds[&oid];
+            if existing != requested {
+                return Err(Error::ObjectKindConflict {
+                    oid,
+                    existing,
+                    requested,
+                });
+            }
+        }
+
+        Ok(rows.into_iter().map(|(oid, _)| oid).collect())
+    }
+}
+
+// internal like `super::write`, so callers can't add their own kinds
+mod add {
+    pub trait Add {
+        fn add_to(&self, writer: &mut super::BulkWriter) -> crate::Result<braid_hash::Oid>;
+    }
+}
+
+impl<S: AsRef<str>> add::Add for CommitData<S> {
+    fn add_to(&self, writer: &mut BulkWriter) -> Result<Oid> {
+        let (oid, encoded) = self.hash_with(writer.scheme)?;
+        let owned = CommitData {
+            register: self.register,
+            parent: self.parent,
+            merge_parent: self.merge_parent,
+            rebase_of: self.rebase_of,
+            saves: self.saves,
+            author: owned_identity(&self.author),
+            author_date: self.author_date,
+            committer: owned_identity(&self.committer),
+            date: self.date,
+            summary: self.summary.as_ref().to_string(),
+            body: self.body.as_ref().to_string(),
+            headers: self
+                .headers
+                .iter()
+                .map(|(key, value)| (key.as_ref().to_string(), value.as_ref().to_string()))
+                .collect(),
+        };
+        writer.push(oid, Some(encoded), Row::Commit(Box::new(owned)))?;
+        Ok(oid)
+    }
+}
+
+fn owned_identity<S: AsRef<str>>(identity: &crate::commit::Identity<S>) -> crate::commit::Identity {
+    crate::commit::Identity::new(
+        identity.name.as_ref().to_string(),
+        identity.email.as_ref().to_string(),
+    )
+}
+
+impl<S: AsRef<str>> add::Add for SaveData<S> {
+    fn add_to(&self, writer: &mut BulkWriter) -> Result<Oid> {
+        let (oid, encoded) = self.hash_with(writer.scheme)?;
+        let owned = SaveData {
+            author: self.author.as_ref().to_string(),
+            date: self.date,
+            content: self.content,
+            parent: self.parent,
+        };
+        writer.push(oid, Some(encoded), Row::Save(owned))?;
+        Ok(oid)
+    }
+}
+
+impl<S: Ord + AsRef<str>> add::Add for RegisterData<S> {
+    fn add_to(&self, writer: &mut BulkWriter) -> Result<Oid> {
+        let (oid, encoded) = self.hash_with(writer.scheme)?;
+        writer.push(oid, Some(encoded), Row::Register(owned_entries(self)))?;
+        Ok(oid)
+    }
+}
+
+impl<S: Ord + AsRef<str>> add::Add for SaveRegisterData<S> {
+    fn add_to(&self, writer: &mut BulkWriter) -> Result<Oid> {
+        let (oid, encoded) = self.hash_with(writer.scheme)?;
+        writer.push(oid, Some(encoded), Row::SaveRegister(owned_entries(self)))?;
+        Ok(oid)
+    }
+}
+
+fn owned_entries<S: AsRef<str>>(data: &impl EntryData<S>) -> Vec<(String, Oid)> {
+    data.iter()
+        .map(|(key, oid)| (key.as_ref().to_string(), *oid))
+        .collect()
+}
+
+/// The rows of a batch by table, bound as one array per column.
+#[derive(Default)]
+struct Columns {
+    object: ObjectColumns,
+    content: (Vec<Oid>, Vec<Oid>),
+    register: Vec<Oid>,
+    register_entry: EntryColumns,
+    save_parent: (Vec<Oid>, Vec<bool>),
+    save: SaveColumns,
+    save_register: Vec<Oid>,
+    save_register_entry: EntryColumns,
+    commit: CommitColumns,
+    commit_header: HeaderColumns,
+}
+
+#[derive(Default)]
+struct ObjectColumns {
+    id: Vec<Oid>,
+    kind: Vec<&'static str>,
+    encoded: Vec<Option<Vec<u8>>>,
+}
+
+#[derive(Default)]
+struct EntryColumns {
+    register: Vec<Oid>,
+    key: Vec<String>,
+    oid: Vec<Oid>,
+}
+
+#[derive(Default)]
+struct SaveColumns {
+    id: Vec<Oid>,
+    author: Vec<String>,
+    date: Vec<OffsetDateTime>,
+    content: Vec<Oid>,
+    parent: Vec<Oid>,
+}
+
+#[derive(Default)]
+struct CommitColumns {
+    id: Vec<Oid>,
+    register: Vec<Oid>,
+    parent: Vec<Option<Oid>>,
+    merge_parent: Vec<Option<Oid>>,
+    rebase_of: Vec<Option<Oid>>,
+    saves: Vec<Oid>,
+    date: Vec<OffsetDateTime>,
+    committer: Vec<String>,
+    committer_email: Vec<String>,
+    author: Vec<String>,
+    author_email: Vec<String>,
+    author_date: Vec<OffsetDateTime>,
+    summary: Vec<String>,
+    body: Vec<String>,
+}
+
+#[derive(Default)]
+struct HeaderColumns {
+    commit: Vec<Oid>,
+    position: Vec<i32>,
+    key: Vec<String>,
+    value: Vec<String>,
+}
+
+impl EntryColumns {
+    fn push(&mut self, register: Oid, entries: &[(String, Oid)]) {
+        for (key, oid) in entries {
+            self.register.push(register);
+            self.key.push(key.clone());
+            self.oid.push(*oid);
+        }
+    }
+}
+
+impl Columns {
+    fn push(&mut self, pending: &Pending) {
+        let id = pending.oid;
+        self.object.id.push(id);
+        self.object.kind.push(pending.row.kind().as_pg_str());
+        self.object.encoded.push(pending.encoded.clone());
+
+        match &pending.row {
+            Row::Content(stream_root) => {
+                self.content.0.push(id);
+                self.content.1.push(*stream_root);
+            }
+            Row::Register(entries) => {
+                self.register.push(id);
+                self.register_entry.push(id, entries);
+            }
+            Row::SaveRegister(entries) => {
+                self.save_register.push(id);
+                self.save_register_entry.push(id, entries);
+            }
+            Row::Save(save) => {
+                self.save_parent.0.push(id);
+                self.save_parent.1.push(false);
+
+                self.save.id.push(id);
+                self.save.author.push(save.author.clone());
+                self.save.date.push(save.date);
+                self.save.content.push(save.content);
+                self.save.parent.push(save.parent);
+            }
+            Row::Commit(commit) => {
+                self.save_parent.0.push(id);
+                self.save_parent.1.push(true);
+
+                let c = &mut self.commit;
+                c.id.push(id);
+                c.register.push(commit.register);
+                c.parent.push(commit.parent);
+                c.merge_parent.push(commit.merge_parent);
+                c.rebase_of.push(commit.rebase_of);
+                c.saves.push(commit.saves);
+                c.date.push(commit.date);
+                c.committer.push(commit.committer.name.clone());
+                c.committer_email.push(commit.committer.email.clone());
+                c.author.push(commit.author.name.clone());
+                c.author_email.push(commit.author.email.clone());
+                c.author_date.push(commit.author_date);
+                c.summary.push(commit.summary.clone());
+                c.body.push(commit.body.clone());
+
+                for (position, (key, value)) in commit.headers.iter().enumerate() {
+                    self.commit_header.commit.push(id);
+                    self.commit_header.position.push(position as i32);
+                    self.commit_header.key.push(key.clone());
+                    self.commit_header.value.push(value.clone());
+                }
+            }
+        }
+    }
+
+    /// Inserts the objects and returns those that were new, the others were
+    /// written by another session since the batch was checked.
+    async fn insert_objects(&self, conn: &mut PgConnection) -> Result<HashSet<Oid>> {
+        if self.object.id.is_empty() {
+            return Ok(HashSet::new());
+        }
+
+        let inserted: Vec<Oid> = sqlx::query_scalar(
+            "INSERT INTO braid.object (id, kind, encoded)
+            SELECT * FROM UNNEST($1::bytea[], $2::text[]::braid.object_kind[], $3::bytea[])
+            ON CONFLICT DO NOTHING
+            RETURNING id",
+        )
+        .bind(&self.object.id)
+        .bind(&self.object.kind)
+        .bind(&self.object.encoded)
+        .fetch_all(conn)
+        .await?;
+        Ok(inserted.into_iter().collect())
+    }
+
+    /// Inserts every table after the tables it references, after
+    /// [`insert_objects`](Self::insert_objects). Foreign keys are checked per
+    /// statement, so objects may reference others in the batch.
+    ///
+    /// The `braid.create_*` procedures insert the same rows one object at a
+    /// time, keep both in sync.
+    async fn insert(&self, conn: &mut PgConnection) -> Result<()> {
+        if !self.content.0.is_empty() {
+            sqlx::query(
+                "INSERT INTO braid.content (id, stream_root)
+                SELECT * FROM UNNEST($1::bytea[], $2::bytea[])
+                ON CONFLICT (id)
+                DO UPDATE SET stream_root = coalesce(braid.content.stream_root, EXCLUDED.stream_root)",
+            )
+            .bind(&self.content.0)
+            .bind(&self.content.1)
+            .execute(&mut *conn)
+            .await?;
+        }
+        insert_ids("braid.register", &self.register, conn).await?;
+        insert_entries(
+            "braid.register_entry (register, key, content)",
+            &self.register_entry,
+            conn,
+        )
+        .await?;
+
+        if !self.save_parent.0.is_empty() {
+            sqlx::query(
+                "INSERT INTO braid.save_parent (id, is_commit)
+                SELECT * FROM UNNEST($1::bytea[], $2::boolean[])
+                ON CONFLICT DO NOTHING",
+            )
+            .bind(&self.save_parent.0)
+            .bind(&self.save_parent.1)
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        let s = &self.save;
+        if !s.id.is_empty() {
+            sqlx::query(
+                "INSERT INTO braid.save (id, author, date, content, parent)
+                SELECT * FROM UNNEST($1::bytea[], $2::varchar[], $3::timestamptz[], $4::bytea[],
+                    $5::bytea[])
+                ON CONFLICT DO NOTHING",
+            )
+            .bind(&s.id)
+            .bind(&s.author)
+            .bind(&s.date)
+            .bind(&s.content)
+            .bind(&s.parent)
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        insert_ids("braid.save_register", &self.save_register, conn).await?;
+        insert_entries(
+            "braid.save_register_entry (save_register, key, save)",
+            &self.save_register_entry,
+            conn,
+        )
+        .await?;
+
+        let c = &self.commit;
+        if !c.id.is_empty() {
+            sqlx::query(
+                "INSERT INTO braid.commit (id, register, parent, merge_parent, rebase_of, saves, date,
+                    committer, committer_email, author, author_email, author_date, summary, body)
+                SELECT * FROM UNNEST($1::bytea[], $2::bytea[], $3::bytea[], $4::bytea[], $5::bytea[],
+                    $6::bytea[], $7::timestamptz[], $8::varchar[], $9::varchar[], $10::varchar[],
+                    $11::varchar[], $12::timestamptz[], $13::text[], $14::text[])
+                ON CONFLICT DO NOTHING",
+            )
+            .bind(&c.id)
+            .bind(&c.register)
+            .bind(&c.parent)
+            .bind(&c.merge_parent)
+            .bind(&c.rebase_of)
+            .bind(&c.saves)
+            .bind(&c.date)
+            .bind(&c.committer)
+            .bind(&c.committer_email)
+            .bind(&c.author)
+            .bind(&c.author_email)
+            .bind(&c.author_date)
+            .bind(&c.summary)
+            .bind(&c.body)
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        let h = &self.commit_header;
+        if !h.commit.is_empty() {
+            sqlx::query(
+                "INSERT INTO braid.commit_header (commit, position, key, value)
+                SELECT * FROM UNNEST($1::bytea[], $2::integer[], $3::varchar[], $4::text[])
+                ON CONFLICT DO NOTHING",
+            )
+            .bind(&h.commit)
+            .bind(&h.position)
+            .bind(&h.key)
+            .bind(&h.value)
+            .execute(&mut *conn)
+            .await?;
+        }
+
+        Ok(())
+    }
+}
+
+async fn insert_ids(table: &str, ids: &[Oid], conn: &mut PgConnection) -> Result<()> {
+    if ids.is_empty() {
+        return Ok(());
+    }
+
+    let sql = format!(
+        "INSERT INTO {table} (id) SELECT * FROM UNNEST($1::bytea[]) ON CONFLICT DO NOTHING"
+    );
+    sqlx::query(&sql).bind(ids).execute(conn).await?;
+    Ok(())
+}
+
+async fn insert_entries(
+    table: &str,
+    entries: &EntryColumns,
+    conn: &mut PgConnection,
+) -> Result<()> {
+    if entries.register.is_empty() {
+        return Ok(());
+    }
+
+    let sql = format!(
+        "INSERT INTO {table}
+        SELECT * FROM UNNEST($1::bytea[], $2::varchar[], $3::bytea[])
+        ON CONFLICT DO NOTHING"
+    );
+    sqlx::query(&sql)
+        .bind(&entries.register)
+        .bind(&entries.key)
+        .bind(&entries.oid)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use braid_hash::{HashAlgorithm, HashScheme, Oid};
+
+    use super::{BulkWriter, Columns};
+    use crate::{
+        bytes::Hash,
+        commit::{CommitData, Identity},
+        postgres::{odb, testing},
+        register::RegisterData,
+        Error, ObjectKind, RegisterEntryKey,
+    };
+
+    fn register(content: Oid) -> RegisterData<String> {
+        let mut data = RegisterData::new();
+        data.insert(
+            RegisterEntryKey::try_from("a".to_string()).unwrap(),
+            content,
+        );
+        data
+    }
+
+    #[tokio::test]
+    async fn test_dedup() {
+        let pool = testing::database("bulk_dedup").await;
+        crate::postgres::init(&pool).await.unwrap();
+        let mut conn = pool.acquire().await.unwrap();
+        let scheme = HashScheme::default();
+        let mut writer = BulkWriter::new(scheme);
+
+        let content = writer.add_content(b"a").unwrap();
+        assert_eq!(writer.add_content(b"a").unwrap(), content);
+        let data = register(content);
+        let oid = writer.add(&data).unwrap();
+        assert_eq!(writer.add(&data).unwrap(), oid);
+        assert_eq!(writer.len(), 2);
+        assert_eq!(writer.flush(&mut conn).await.unwrap(), 2);
+        assert!(writer.is_empty());
+
+        // objects that were written before are skipped
+        writer.add(&data).unwrap();
+        writer.add_content(b"b").unwrap();
+        assert_eq!(writer.flush(&mut conn).await.unwrap(), 1);
+        let read = odb::verified::get_register(oid, scheme, &mut *conn)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(read.data().get("a"), Some(&content));
+    }
+
+    #[tokio::test]
+    async fn test_stream_root() {
+        let pool = testing::database("bulk_stream_root").await;
+        crate::postgres::init(&pool).await.unwrap();
+        let mut conn = pool.acquire().await.unwrap();
+        let scheme = HashScheme::default();
+
+        // content recorded before stream roots were
+        let content = crate::content_oid(scheme, b"a");
+        sqlx::query("CALL braid.create_content($1, NULL)")
+            .bind(content)
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+
+        let mut writer = BulkWriter::new(scheme);
+        writer.add_content(b"a").unwrap();
+        assert_eq!(writer.flush(&mut conn).await.unwrap(), 0);
+
+        let stream_root: Option<Oid> =
+            sqlx::query_scalar("SELECT stream_root FROM braid.content WHERE id = $1")
+                .bind(content)
+                .fetch_one(&mut *conn)
+                .await
+                .unwrap();
+        assert_eq!(stream_root, Some(braid_hash::hash(b"a")));
+    }
+
+    #[tokio::test]
+    async fn test_kind_conflict() {
+        let pool = testing::database("bulk_kind_conflict").await;
+        // without domains, content can have the oid of a register
+        let scheme = HashScheme::new(HashAlgorithm::Blake3);
+        crate::postgres::init_with(&pool, scheme).await.unwrap();
+        let mut conn = pool.acquire().await.unwrap();
+        let conflict = |result| {
+            matches!(
+                result,
+                Err(Error::ObjectKindConflict {
+                    existing: ObjectKind::Register,
+                    requested: ObjectKind::Content,
+                    ..
+                })
+            )
+        };
+
+        let (_, empty) = RegisterData::<String>::new().hash_with(scheme).unwrap();
+        let mut writer = BulkWriter::new(scheme);
+        writer.add(&RegisterData::<String>::new()).unwrap();
+        assert!(conflict(writer.add_content(&empty).map(|_| 0)));
+
+        // the empty register was written by `init`
+        let mut writer = BulkWriter::new(scheme);
+        writer.add_content(&empty).unwrap();
+        assert!(conflict(writer.flush(&mut conn).await));
+
+        // a register written by another session while the batch is flushed
+        let (oid, encoded) = register(Oid::repeat(1)).hash_with(scheme).unwrap();
+        let mut other = pool.begin().await.unwrap();
+        sqlx::query("INSERT INTO braid.object (id, kind, encoded) VALUES ($1, 'register', $2)")
+            .bind(oid)
+            .bind(&encoded)
+            .execute(&mut *other)
+            .await
+            .unwrap();
+
+        let mut writer = BulkWriter::new(scheme);
+        writer.add_content(&encoded).unwrap();
+        let commit = async {
+            // commit once the flush waits for this transaction
+            let mut watcher = pool.acquire().await.unwrap();
+            loop {
+                let waiting: bool = sqlx::query_scalar(
+                    "SELECT EXISTS (SELECT FROM pg_stat_activity
+                        WHERE datname = current_database() AND wait_event_type = 'Lock')",
+                )
+                .fetch_one(&mut *watcher)
+                .await
+                .unwrap();
+                if waiting {
+                    break;
+                }
+            }
+            other.commit().await.unwrap();
+        };
+        let (result, ()) = tokio::join!(writer.flush(&mut conn), commit);
+        assert!(conflict(result));
+    }
+
+    #[test]
+    fn test_header_positions() {
+        let commit = |summary: &'static str| {
+            CommitData::new(
+                Oid::repeat(1),
+                Oid::repeat(2),
+                None,
+                None,
+                Oid::repeat(3),
+                time::OffsetDateTime::UNIX_EPOCH,
+                Identity::new("alice", ""),
+                summary,
+                "",
+            )
+        };
+        let first = commit("first")
+            .with_header("B", "1")
+            .and_then(|data| data.with_header("A", "2"))
+            .and_then(|data| data.with_header("B", "3"))
+            .unwrap();
+        let second = commit("second").with_header("A", "4").unwrap();
+
+        let mut writer = BulkWriter::new(HashScheme::default());
+        let first = writer.add(&first).unwrap();
+        let second = writer.add(&second).unwrap();
+        let mut columns = Columns::default();
+        for pending in &writer.pending {
+            columns.push(pending);
+        }
+
+        // positions keep the order of each commit's headers
+        let headers = &columns.commit_header;
+        assert_eq!(headers.commit, [first, first, first, second]);
+        assert_eq!(headers.position, [0, 1, 2, 0]);
+        assert_eq!(headers.key, ["B", "A", "B", "A"]);
+        assert_eq!(headers.value, ["1", "2", "3", "4"]);
+    }
+}
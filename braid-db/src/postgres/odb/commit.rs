@@ -1,12 +1,16 @@
-use braid_hash::Oid;
+use braid_hash::{HashScheme, Oid};
+use sqlx::{
+    postgres::{PgHasArrayType, PgRow, PgTypeInfo},
+    FromRow, Postgres, Row, Type,
+};
 
 use crate::{
     bytes::Hash,
-    commit::{Commit, CommitData},
+    commit::{Commit, CommitData, Identity},
     Result,
 };
 
-use super::OidData;
+use super::{register::Varchar, OidData};
 
 pub(super) async fn get(
     oid: Oid,
@@ -19,11 +23,35 @@ pub(super) async fn get(
     Ok(commit)
 }
 
+pub(super) async fn get_many(
+    oids: &[Oid],
+    exec: impl super::Executor<'_>,
+) -> Result<Vec<Commit<String>>> {
+    let commits = sqlx::query_as(
+        "SELECT c.* FROM UNNEST($1::bytea[]) AS o(id), LATERAL braid.get_commit(o.id) AS c",
+    )
+    .bind(oids)
+    .fetch_all(exec)
+    .await?;
+    Ok(commits)
+}
+
 impl<S: AsRef<str>> super::write::Write for CommitData<S> {
-    async fn write(&self, exec: impl super::Executor<'_>) -> Result<Oid> {
-        let (id, _) = Hash::hash(self)?;
+    async fn write(&self, scheme: HashScheme, exec: impl super::Executor<'_>) -> Result<Oid> {
+        let (id, encoded) = self.hash_with(scheme)?;
+
+        let headers: Vec<_> = self
+            .headers
+            .iter()
+            .map(|(key, value)| Header {
+                key: Varchar(key.as_ref().to_string()),
+                value: value.as_ref().to_string(),
+            })
+            .collect();
 
-        sqlx::query("CALL braid.create_commit($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)")
+        sqlx::query(
+            "CALL braid.create_commit($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16)",
+        )
             .bind(id)
             .bind(self.register)
             .bind(self.parent)
@@ -31,9 +59,15 @@ impl<S: AsRef<str>> super::write::Write for CommitData<S> {
             .bind(self.rebase_of)
             .bind(self.saves)
             .bind(self.date)
-            .bind(self.committer.as_ref())
+            .bind(self.committer.name.as_ref())
+            .bind(self.committer.email.as_ref())
+            .bind(self.author.name.as_ref())
+            .bind(self.author.email.as_ref())
+            .bind(self.author_date)
             .bind(self.summary.as_ref())
             .bind(self.body.as_ref())
+            .bind(&headers)
+            .bind(encoded)
             .execute(exec)
             .await?;
 
@@ -41,6 +75,59 @@ impl<S: AsRef<str>> super::write::Write for CommitData<S> {
     }
 }
 
+pub(super) async fn find_by_header(
+    key: &str,
+    value: Option<&str>,
+    exec: impl super::Executor<'_>,
+) -> Result<Vec<Oid>> {
+    let commits = sqlx::query_scalar("SELECT * FROM braid.find_commits_by_header($1, $2)")
+        .bind(key)
+        .bind(value)
+        .fetch_all(exec)
+        .await?;
+    Ok(commits)
+}
+
+#[derive(sqlx::Encode)]
+struct Header {
+    key: Varchar,
+    value: String,
+}
+
+impl Type<Postgres> for Header {
+    fn type_info() -> PgTypeInfo {
+        PgTypeInfo::with_name("braid.header_record")
+    }
+}
+
+impl PgHasArrayType for Header {
+    fn array_type_info() -> PgTypeInfo {
+        PgTypeInfo::with_name("braid.header_records")
+    }
+}
+
+impl<'r> FromRow<'r, PgRow> for CommitData<String> {
+    fn from_row(row: &'r PgRow) -> std::result::Result<Self, sqlx::Error> {
+        let keys: Vec<String> = row.try_get("header_keys")?;
+        let values: Vec<String> = row.try_get("header_values")?;
+
+        Ok(Self {
+            register: row.try_get("register")?,
+            parent: row.try_get("parent")?,
+            merge_parent: row.try_get("merge_parent")?,
+            rebase_of: row.try_get("rebase_of")?,
+            saves: row.try_get("saves")?,
+            author: Identity::new(row.try_get("author")?, row.try_get("author_email")?),
+            author_date: row.try_get("author_date")?,
+            committer: Identity::new(row.try_get("committer")?, row.try_get("committer_email")?),
+            date: row.try_get("date")?,
+            summary: row.try_get("summary")?,
+            body: row.try_get("body")?,
+            headers: keys.into_iter().zip(values).collect(),
+        })
+    }
+}
+
 impl OidData<'_> for Commit<String> {
     type Data = CommitData<String>;
 
@@ -1,7 +1,15 @@
-use braid_hash::Oid;
+use std::collections::HashSet;
+
+use braid_hash::{HashScheme, Oid};
 use sqlx::postgres::PgRow;
 
-use crate::{commit::Commit, register::{Register, SaveRegister}, save::Save, Result};
+use crate::{
+    commit::Commit,
+    oid::ValidOid,
+    register::{Register, SaveRegister},
+    save::Save,
+    Error, ObjectKind, Result,
+};
 
 use super::Executor;
 
@@ -9,6 +17,9 @@ mod commit;
 mod register;
 mod save;
 
+pub mod bulk;
+pub mod verified;
+
 pub async fn get_commit(
     oid: Oid,
     exec: impl Executor<'_>,
@@ -16,10 +27,17 @@ pub async fn get_commit(
     commit::get(oid, exec).await
 }
 
-pub async fn get_register(
-    oid: Oid,
+/// Finds the commits with an extension header `key`, optionally only those
+/// where it has `value`.
+pub async fn find_commits_by_header(
+    key: &str,
+    value: Option<&str>,
     exec: impl Executor<'_>,
-) -> Result<Option<Register>> {
+) -> Result<Vec<Oid>> {
+    commit::find_by_header(key, value, exec).await
+}
+
+pub async fn get_register(oid: Oid, exec: impl Executor<'_>) -> Result<Option<Register>> {
     register::get_register(oid, exec).await
 }
 
@@ -34,23 +52,137 @@ pub async fn get_save_register(
     register::get_save_register(oid, exec).await
 }
 
-pub async fn create_content(oid: Oid, exec: impl Executor<'_>) -> Result<()> {
-    sqlx::query("CALL braid.create_content($1)")
+/// Reads the commits among `oids`, in no particular order. Missing commits are
+/// left out.
+pub async fn get_commits(oids: &[Oid], exec: impl Executor<'_>) -> Result<Vec<Commit>> {
+    commit::get_many(oids, exec).await
+}
+
+/// Like [`get_commits`], for registers.
+pub async fn get_registers(oids: &[Oid], exec: impl Executor<'_>) -> Result<Vec<Register>> {
+    register::get_registers(oids, exec).await
+}
+
+/// Like [`get_commits`], for saves.
+pub async fn get_saves(oids: &[Oid], exec: impl Executor<'_>) -> Result<Vec<Save>> {
+    save::get_many(oids, exec).await
+}
+
+/// Like [`get_commits`], for save registers.
+pub async fn get_save_registers(
+    oids: &[Oid],
+    exec: impl Executor<'_>,
+) -> Result<Vec<SaveRegister<String>>> {
+    register::get_save_registers(oids, exec).await
+}
+
+/// Records content stored elsewhere. `oid` and `stream_root` are trusted to be
+/// its [`content_oid`](crate::content_oid) under the repository's scheme and
+/// its [`braid_hash::hash`], public callers go through [`write_content`].
+pub(super) async fn create_content(
+    oid: Oid,
+    stream_root: Oid,
+    exec: impl Executor<'_>,
+) -> Result<()> {
+    sqlx::query("CALL braid.create_content($1, $2)")
         .bind(oid)
+        .bind(stream_root)
         .execute(exec)
         .await?;
     Ok(())
 }
 
-pub async fn write(obj: &impl write::Write, exec: impl Executor<'_>) -> Result<Oid> {
-    obj.write(exec).await
+/// Records content stored elsewhere by its bytes and returns its oid.
+pub async fn write_content(
+    data: &[u8],
+    scheme: HashScheme,
+    exec: impl Executor<'_>,
+) -> Result<Oid> {
+    let oid = crate::content_oid(scheme, data);
+    create_content(oid, braid_hash::hash(data), exec).await?;
+    Ok(oid)
+}
+
+/// The hash that verified streams of content are checked against, see
+/// [`braid_hash::stream`]. `None` if the content is missing or was recorded
+/// before stream roots were, without its bytes.
+pub async fn stream_root(oid: Oid, exec: impl Executor<'_>) -> Result<Option<Oid>> {
+    let root = sqlx::query_scalar("SELECT stream_root FROM braid.content WHERE id = $1")
+        .bind(oid)
+        .fetch_optional(exec)
+        .await?;
+    Ok(root.flatten())
+}
+
+/// Reads the canonical encoding of an object, the bytes its oid is the hash of.
+///
+/// Content has no canonical encoding and fails with [`Error::UnencodedKind`].
+pub async fn read_raw(oid: Oid, exec: impl Executor<'_>) -> Result<Option<(ObjectKind, Vec<u8>)>> {
+    let row: Option<(ObjectKind, Option<Vec<u8>>)> =
+        sqlx::query_as("SELECT * FROM braid.read_raw($1)")
+            .bind(oid)
+            .fetch_optional(exec)
+            .await?;
+
+    match row {
+        None => Ok(None),
+        Some((kind, Some(encoded))) => Ok(Some((kind, encoded))),
+        Some((kind, None)) => Err(Error::UnencodedKind(kind)),
+    }
+}
+
+/// Looks up the oid an object got when it was copied here by
+/// [`rehash`](crate::postgres::rehash::rehash) under its old oid `legacy`.
+pub async fn resolve_legacy(legacy: Oid, exec: impl Executor<'_>) -> Result<Option<Oid>> {
+    let oid = sqlx::query_scalar("SELECT id FROM braid.legacy_oid WHERE legacy = $1")
+        .bind(legacy)
+        .fetch_optional(exec)
+        .await?;
+    Ok(oid)
+}
+
+/// Returns which of `oids` exist, whatever their kind.
+pub async fn existing(oids: &[Oid], exec: impl Executor<'_>) -> Result<HashSet<Oid>> {
+    let existing: Vec<Oid> = sqlx::query_scalar("SELECT id FROM braid.object WHERE id = ANY($1)")
+        .bind(oids)
+        .fetch_all(exec)
+        .await?;
+    Ok(existing.into_iter().collect())
+}
+
+/// Checks that `oid` exists with the kind of `V`.
+pub async fn validate<V: ValidOid>(oid: Oid, exec: impl Executor<'_>) -> Result<V> {
+    let kind: Option<ObjectKind> =
+        sqlx::query_scalar("SELECT kind FROM braid.object WHERE id = $1")
+            .bind(oid)
+            .fetch_optional(exec)
+            .await?;
+
+    match kind {
+        Some(kind) if kind == V::KIND => Ok(V::new(oid)),
+        _ => Err(Error::ObjectNotFound(V::KIND, oid)),
+    }
+}
+
+/// Writes an object hashed with `scheme`, which must be the scheme of the
+/// repository.
+pub async fn write(
+    obj: &impl write::Write,
+    scheme: HashScheme,
+    exec: impl Executor<'_>,
+) -> Result<Oid> {
+    obj.write(scheme, exec).await
 }
 
 // this is in an internal trait and just a way to genericize the `write_to_tran` function
 #[allow(async_fn_in_trait)]
 mod write {
     pub trait Write {
-        async fn write(&self, exec: impl super::Executor<'_>) -> crate::Result<braid_hash::Oid>;
+        async fn write(
+            &self,
+            scheme: braid_hash::HashScheme,
+            exec: impl super::Executor<'_>,
+        ) -> crate::Result<braid_hash::Oid>;
     }
 }
 
@@ -81,3 +213,37 @@ macro_rules! impl_from_row {
 }
 
 impl_from_row!(Commit<String>, Save<String>);
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use braid_hash::{stream, Oid};
+
+    use crate::postgres::testing;
+
+    #[tokio::test]
+    async fn test_stream_root() {
+        let pool = testing::database("odb_stream_root").await;
+        crate::postgres::init(&pool).await.unwrap();
+        let mut conn = pool.acquire().await.unwrap();
+        let scheme = crate::postgres::hash_scheme(&mut *conn).await.unwrap();
+
+        let data: Vec<u8> = (0..5000).map(|i| i as u8).collect();
+        let oid = super::write_content(&data, scheme, &mut *conn)
+            .await
+            .unwrap();
+        let root = super::stream_root(oid, &mut *conn).await.unwrap().unwrap();
+        assert_ne!(root, oid);
+
+        // a slice from an untrusted cache verifies against the recorded root
+        let (_, outboard) = stream::outboard(&data);
+        let slice = stream::extract_slice(&outboard, Cursor::new(&data), 2000, 100).unwrap();
+        let mut out = Vec::new();
+        stream::decode_slice(&root, slice.as_slice(), 2000, 100, &mut out).unwrap();
+        assert_eq!(out, &data[2000..2100]);
+
+        let missing = super::stream_root(Oid::repeat(1), &mut *conn).await;
+        assert_eq!(missing.unwrap(), None);
+    }
+}
@@ -1,30 +1,36 @@
-use braid_hash::Oid;
+use std::collections::HashMap;
+
+use braid_hash::{HashScheme, Oid};
 use sqlx::{
     postgres::{PgHasArrayType, PgTypeInfo},
     Postgres, Type,
 };
 
 use crate::{
-    bytes::Hash, postgres::Executor, register::{EntryData, Register, RegisterData, RegisterKind, SaveRegister, SaveRegisterData}, Key, Result
+    bytes::Hash,
+    postgres::Executor,
+    register::{EntryData, Register, RegisterData, RegisterKind, SaveRegister, SaveRegisterData},
+    Key, Result,
 };
 
 impl<S: Ord + AsRef<str>> super::write::Write for RegisterData<S> {
-    async fn write(&self, exec: impl Executor<'_>) -> crate::Result<Oid> {
-        write(self, exec).await
+    async fn write(&self, scheme: HashScheme, exec: impl Executor<'_>) -> crate::Result<Oid> {
+        write(self, scheme, exec).await
     }
 }
 
 impl<S: Ord + AsRef<str>> super::write::Write for SaveRegisterData<S> {
-    async fn write(&self, exec: impl Executor<'_>) -> crate::Result<Oid> {
-        write(self, exec).await
+    async fn write(&self, scheme: HashScheme, exec: impl Executor<'_>) -> crate::Result<Oid> {
+        write(self, scheme, exec).await
     }
 }
 
 async fn write<S: AsRef<str>, R: EntryData<S> + Hash>(
     data: &R,
+    scheme: HashScheme,
     exec: impl Executor<'_>,
 ) -> Result<Oid> {
-    let (oid, _) = Hash::hash(&data)?;
+    let (oid, encoded) = data.hash_with(scheme)?;
 
     let mut entries = Vec::with_capacity(data.len());
 
@@ -36,13 +42,14 @@ async fn write<S: AsRef<str>, R: EntryData<S> + Hash>(
     }
 
     let call = match R::REGISTER_KIND {
-        RegisterKind::Register => "CALL braid.create_register($1, $2);",
-        RegisterKind::SaveRegister => "CALL braid.create_save_register($1, $2);",
+        RegisterKind::Register => "CALL braid.create_register($1, $2, $3);",
+        RegisterKind::SaveRegister => "CALL braid.create_save_register($1, $2, $3);",
     };
 
     sqlx::query(call)
         .bind(oid)
         .bind(&entries)
+        .bind(encoded)
         .execute(exec)
         .await?;
 
@@ -50,24 +57,28 @@ async fn write<S: AsRef<str>, R: EntryData<S> + Hash>(
 }
 
 pub(crate) async fn get_register(id: Oid, exec: impl Executor<'_>) -> Result<Option<Register>> {
-    get(id, exec).await.map(|o| o.map(|data| Register { id, data }))
+    get(id, exec)
+        .await
+        .map(|o| o.map(|data| Register { id, data }))
 }
 
-pub(crate) async fn get_save_register(id: Oid, exec: impl Executor<'_>) -> Result<Option<SaveRegister>> {
-    get(id, exec).await.map(|o| o.map(|data| SaveRegister { id, data }))
+pub(crate) async fn get_save_register(
+    id: Oid,
+    exec: impl Executor<'_>,
+) -> Result<Option<SaveRegister>> {
+    get(id, exec)
+        .await
+        .map(|o| o.map(|data| SaveRegister { id, data }))
 }
 
 async fn get<R: EntryData<String>>(id: Oid, exec: impl Executor<'_>) -> Result<Option<R>> {
-    if id == R::EMPTY_ID {
-        return Ok(Some(R::new()));
-    }
-
     let select = match R::REGISTER_KIND {
         RegisterKind::Register => "SELECT * FROM braid.get_register($1);",
         RegisterKind::SaveRegister => "SELECT * FROM braid.get_save_register($1);",
     };
 
-    let entries = sqlx::query_as(select)
+    // an existing register without entries is a single row of NULLs
+    let entries: Vec<(Option<String>, Option<Oid>)> = sqlx::query_as(select)
         .bind(id.as_bytes())
         .fetch_all(exec)
         .await?;
@@ -78,19 +89,63 @@ async fn get<R: EntryData<String>>(id: Oid, exec: impl Executor<'_>) -> Result<O
 
     let mut data = R::new();
 
-    for Entry {
-        key,
-        content,
-    } in entries
-    {
-        let key = Key::try_from(key.0)?;
-        data.insert(key, content);
+    for (key, content) in entries {
+        if let (Some(key), Some(content)) = (key, content) {
+            data.insert(Key::try_from(key)?, content);
+        }
     }
 
     Ok(Some(data))
 }
 
-#[derive(sqlx::Encode, sqlx::Decode, sqlx::FromRow)]
+pub(crate) async fn get_registers(ids: &[Oid], exec: impl Executor<'_>) -> Result<Vec<Register>> {
+    let registers = get_many(ids, exec).await?;
+    Ok(registers
+        .into_iter()
+        .map(|(id, data)| Register { id, data })
+        .collect())
+}
+
+pub(crate) async fn get_save_registers(
+    ids: &[Oid],
+    exec: impl Executor<'_>,
+) -> Result<Vec<SaveRegister>> {
+    let registers = get_many(ids, exec).await?;
+    Ok(registers
+        .into_iter()
+        .map(|(id, data)| SaveRegister { id, data })
+        .collect())
+}
+
+async fn get_many<R: EntryData<String>>(
+    ids: &[Oid],
+    exec: impl Executor<'_>,
+) -> Result<HashMap<Oid, R>> {
+    let select = match R::REGISTER_KIND {
+        RegisterKind::Register => {
+            "SELECT o.id, r.* FROM UNNEST($1::bytea[]) AS o(id), LATERAL braid.get_register(o.id) AS r"
+        }
+        RegisterKind::SaveRegister => {
+            "SELECT o.id, r.* FROM UNNEST($1::bytea[]) AS o(id), LATERAL braid.get_save_register(o.id) AS r"
+        }
+    };
+
+    let rows: Vec<(Oid, Option<String>, Option<Oid>)> =
+        sqlx::query_as(select).bind(ids).fetch_all(exec).await?;
+
+    let mut registers: HashMap<Oid, R> = HashMap::new();
+
+    for (id, key, content) in rows {
+        let data = registers.entry(id).or_insert_with(R::new);
+        if let (Some(key), Some(content)) = (key, content) {
+            data.insert(Key::try_from(key)?, content);
+        }
+    }
+
+    Ok(registers)
+}
+
+#[derive(sqlx::Encode)]
 struct Entry {
     key: Varchar,
     content: Oid,
@@ -109,7 +164,7 @@ impl PgHasArrayType for Entry {
 }
 
 #[derive(sqlx::Encode, sqlx::Decode)]
-struct Varchar(String);
+pub(super) struct Varchar(pub(super) String);
 
 impl Type<Postgres> for Varchar {
     fn type_info() -> PgTypeInfo {
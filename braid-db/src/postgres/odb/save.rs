@@ -1,4 +1,4 @@
-use braid_hash::Oid;
+use braid_hash::{HashScheme, Oid};
 
 use crate::{
     bytes::Hash,
@@ -17,16 +17,27 @@ pub(super) async fn get(oid: Oid, exec: impl Executor<'_>) -> Result<Option<Save
     Ok(save)
 }
 
+pub(super) async fn get_many(oids: &[Oid], exec: impl Executor<'_>) -> Result<Vec<Save<String>>> {
+    let saves = sqlx::query_as(
+        "SELECT s.* FROM UNNEST($1::bytea[]) AS o(id), LATERAL braid.get_save(o.id) AS s",
+    )
+    .bind(oids)
+    .fetch_all(exec)
+    .await?;
+    Ok(saves)
+}
+
 impl<S: AsRef<str>> super::write::Write for SaveData<S> {
-    async fn write(&self, exec: impl super::Executor<'_>) -> Result<Oid> {
-        let (id, _) = Hash::hash(self)?;
+    async fn write(&self, scheme: HashScheme, exec: impl super::Executor<'_>) -> Result<Oid> {
+        let (id, encoded) = self.hash_with(scheme)?;
 
-        sqlx::query("CALL braid.create_save($1, $2::varchar, $3, $4, $5)")
+        sqlx::query("CALL braid.create_save($1, $2::varchar, $3, $4, $5, $6)")
             .bind(id)
             .bind(self.author.as_ref())
             .bind(self.date)
             .bind(self.content)
             .bind(self.parent)
+            .bind(encoded)
             .execute(exec)
             .await?;
 
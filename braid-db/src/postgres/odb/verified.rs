@@ -0,0 +1,154 @@
+//! Reads that hash the stored canonical encoding and fail with
+//! [`Error::HashMismatch`](crate::Error::HashMismatch) if it does not match
+//! the requested oid, so corrupted or tampered rows are never served. Objects
+//! are decoded from that encoding, which keeps dates exactly, unlike the
+//! `timestamptz` columns. Objects are hashed with the scheme passed in, which
+//! must be the scheme of the repository.
+
+use braid_hash::{HashScheme, Oid};
+
+use crate::{
+    bytes::Hash,
+    commit::Commit,
+    postgres::Executor,
+    register::{Register, SaveRegister},
+    save::Save,
+    Error, Object, ObjectData, ObjectKind, Result,
+};
+
+pub async fn get_commit(
+    oid: Oid,
+    scheme: HashScheme,
+    exec: impl Executor<'_>,
+) -> Result<Option<Commit>> {
+    match get(oid, scheme, exec).await? {
+        Some(ObjectData::Commit(data)) => Ok(Some(Commit { id: oid, data })),
+        _ => Ok(None),
+    }
+}
+
+pub async fn get_register(
+    oid: Oid,
+    scheme: HashScheme,
+    exec: impl Executor<'_>,
+) -> Result<Option<Register>> {
+    match get(oid, scheme, exec).await? {
+        Some(ObjectData::Register(data)) => Ok(Some(Register { id: oid, data })),
+        _ => Ok(None),
+    }
+}
+
+pub async fn get_save(
+    oid: Oid,
+    scheme: HashScheme,
+    exec: impl Executor<'_>,
+) -> Result<Option<Save>> {
+    match get(oid, scheme, exec).await? {
+        Some(ObjectData::Save(data)) => Ok(Some(Save { id: oid, data })),
+        _ => Ok(None),
+    }
+}
+
+pub async fn get_save_register(
+    oid: Oid,
+    scheme: HashScheme,
+    exec: impl Executor<'_>,
+) -> Result<Option<SaveRegister>> {
+    match get(oid, scheme, exec).await? {
+        Some(ObjectData::SaveRegister(data)) => Ok(Some(SaveRegister { id: oid, data })),
+        _ => Ok(None),
+    }
+}
+
+pub async fn read_raw(
+    oid: Oid,
+    scheme: HashScheme,
+    exec: impl Executor<'_>,
+) -> Result<Option<(ObjectKind, Vec<u8>)>> {
+    let raw = super::read_raw(oid, exec).await?;
+    if let Some((kind, encoded)) = &raw {
+        let actual = scheme.hash(kind.domain(), encoded);
+        if actual != oid {
+            return Err(Error::HashMismatch {
+                expected: oid,
+                actual,
+            });
+        }
+    }
+    Ok(raw)
+}
+
+/// Decodes the verified encoding of `oid`, `None` if it is missing or content.
+async fn get(oid: Oid, scheme: HashScheme, exec: impl Executor<'_>) -> Result<Option<ObjectData>> {
+    match read_raw(oid, scheme, exec).await {
+        Ok(Some((_, encoded))) => Ok(Some(Object::decode(&encoded, scheme)?.1)),
+        Ok(None) | Err(Error::UnencodedKind(ObjectKind::Content)) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+pub(crate) fn verify(expected: Oid, data: &impl Hash, scheme: HashScheme) -> Result<()> {
+    let (actual, _) = data.hash_with(scheme)?;
+    if actual == expected {
+        Ok(())
+    } else {
+        Err(Error::HashMismatch { expected, actual })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::{OffsetDateTime, UtcOffset};
+
+    use crate::{
+        bytes::Hash,
+        commit::{CommitData, Identity},
+        postgres::{odb, testing},
+        save::SaveData,
+    };
+
+    #[tokio::test]
+    async fn test_dates() {
+        let pool = testing::database("verified_dates").await;
+        crate::postgres::init(&pool).await.unwrap();
+        let mut conn = pool.acquire().await.unwrap();
+        let scheme = crate::postgres::hash_scheme(&mut *conn).await.unwrap();
+
+        // `timestamptz` keeps neither the offset nor the nanoseconds
+        let date = OffsetDateTime::from_unix_timestamp_nanos(1_700_000_000_123_456_789)
+            .unwrap()
+            .to_offset(UtcOffset::from_hms(1, 0, 0).unwrap());
+        let root = CommitData::root(scheme).unwrap();
+        let (root_id, _) = root.hash_with(scheme).unwrap();
+        let commit = CommitData::new(
+            root.register(),
+            root_id,
+            None,
+            None,
+            root.saves(),
+            date,
+            Identity::new("alice", ""),
+            "dated",
+            "",
+        );
+        let commit = odb::write(&commit, scheme, &mut *conn).await.unwrap();
+        let content = odb::write_content(b"a", scheme, &mut *conn).await.unwrap();
+        let save = SaveData::new("alice", date, content, commit);
+        let save = odb::write(&save, scheme, &mut *conn).await.unwrap();
+
+        let read = super::get_commit(commit, scheme, &mut *conn)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(read.data().date().offset(), date.offset());
+        assert_eq!(read.data().date().nanosecond(), date.nanosecond());
+        assert_eq!(read.data().author_date(), date);
+
+        let read = super::get_save(save, scheme, &mut *conn)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(read.data().date().offset(), date.offset());
+        assert_eq!(read.data().date().nanosecond(), date.nanosecond());
+    }
+}
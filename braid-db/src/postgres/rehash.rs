@@ -0,0 +1,222 @@
+//! Moves objects to a repository with another hash scheme, such as one that
+//! hashes in domains. The scheme of a repository can't change in place since
+//! every oid depends on it.
+
+use std::collections::{HashMap, HashSet};
+
+use braid_hash::Oid;
+use sqlx::PgConnection;
+
+use crate::{
+    bundle::{ContentSink, ContentSource, Ref},
+    Error, Object, ObjectData, Result,
+};
+
+use super::{
+    bundle::{Step, Walk},
+    odb,
+};
+
+/// Copies everything reachable from `refs` in `source` to `target`, hashing
+/// it with the scheme of `target`, and returns the refs with their new oids.
+///
+/// `target` is an initialized repository in another database. Content bytes
+/// are read from `source_content` and handed to `target_content` with their
+/// new oid. Every object and content is first verified against its old oid
+/// under the scheme of `source`. The old oid of every copied object is recorded in `target`, see
+/// [`odb::resolve_legacy`], so an interrupted run can be repeated and oids
+/// kept outside the repository can still be resolved.
+pub async fn rehash(
+    source: &mut PgConnection,
+    target: &mut PgConnection,
+    refs: &[Ref],
+    source_content: &mut impl ContentSource,
+    target_content: &mut impl ContentSink,
+) -> Result<Vec<Ref>> {
+    let source_scheme = super::hash_scheme(&mut *source).await?;
+    let scheme = super::hash_scheme(&mut *target).await?;
+    let mut map = HashMap::new();
+    let mut seen = HashSet::new();
+
+    let mut walk = Walk::new(refs.iter().map(|r| r.oid), source_scheme);
+    while let Some(step) = walk.next(&mut seen, source).await? {
+        let legacy = match &step {
+            Step::Content(oid) | Step::Object(oid, _) => *oid,
+        };
+        if let Some(oid) = odb::resolve_legacy(legacy, &mut *target).await? {
+            map.insert(legacy, oid);
+            continue;
+        }
+
+        let oid = match step {
+            Step::Content(_) => {
+                let bytes = source_content.read_content(legacy)?;
+                let actual = crate::content_oid(source_scheme, &bytes);
+                if actual != legacy {
+                    return Err(Error::HashMismatch {
+                        expected: legacy,
+                        actual,
+                    });
+                }
+                let oid = crate::content_oid(scheme, &bytes);
+                target_content.write_content(oid, &bytes)?;
+                odb::create_content(oid, braid_hash::hash(&bytes), &mut *target).await?;
+                oid
+            }
+            Step::Object(_, encoded) => {
+                let (_, mut data) = Object::decode(&encoded, source_scheme)?;
+                remap(&mut data, &map)?;
+                match &data {
+                    ObjectData::Commit(data) => odb::write(data, scheme, &mut *target).await?,
+                    ObjectData::Save(data) => odb::write(data, scheme, &mut *target).await?,
+                    ObjectData::Register(data) => odb::write(data, scheme, &mut *target).await?,
+                    ObjectData::SaveRegister(data) => {
+                        odb::write(data, scheme, &mut *target).await?
+                    }
+                }
+            }
+        };
+
+        sqlx::query(
+            "INSERT INTO braid.legacy_oid (legacy, id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+        )
+        .bind(legacy)
+        .bind(oid)
+        .execute(&mut *target)
+        .await?;
+        map.insert(legacy, oid);
+    }
+
+    refs.iter()
+        .map(|r| {
+            Ok(Ref {
+                name: r.name.clone(),
+                oid: lookup(&map, r.oid)?,
+            })
+        })
+        .collect()
+}
+
+/// Replaces every reference in `data` with its new oid.
+fn remap(data: &mut ObjectData, map: &HashMap<Oid, Oid>) -> Result<()> {
+    match data {
+        ObjectData::Commit(commit) => {
+            commit.register = lookup(map, commit.register)?;
+            commit.saves = lookup(map, commit.saves)?;
+            let parents = [
+                &mut commit.parent,
+                &mut commit.merge_parent,
+                &mut commit.rebase_of,
+            ];
+            for oid in parents.into_iter().flatten() {
+                *oid = lookup(map, *oid)?;
+            }
+        }
+        ObjectData::Save(save) => {
+            save.content = lookup(map, save.content)?;
+            save.parent = lookup(map, save.parent)?;
+        }
+        ObjectData::Register(register) => register.map_oids(|oid| lookup(map, oid))?,
+        ObjectData::SaveRegister(register) => register.map_oids(|oid| lookup(map, oid))?,
+    }
+    Ok(())
+}
+
+fn lookup(map: &HashMap<Oid, Oid>, oid: Oid) -> Result<Oid> {
+    map.get(&oid)
+        .copied()
+        .ok_or(Error::MissingReference { oid, kind: None })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use braid_hash::{HashAlgorithm, HashScheme, Oid};
+    use time::OffsetDateTime;
+
+    use crate::{
+        bundle::{ContentSink, ContentSource, Ref},
+        bytes::Hash,
+        commit::{CommitData, Identity},
+        postgres::{odb, testing},
+        register::RegisterData,
+        Error, RegisterEntryKey,
+    };
+
+    struct Contents(HashMap<Oid, Vec<u8>>);
+
+    impl ContentSource for Contents {
+        fn read_content(&mut self, oid: Oid) -> std::io::Result<Vec<u8>> {
+            Ok(self.0[&oid].clone())
+        }
+    }
+
+    impl ContentSink for Contents {
+        fn write_content(&mut self, oid: Oid, bytes: &[u8]) -> std::io::Result<()> {
+            self.0.insert(oid, bytes.to_vec());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rehash() {
+        let source = testing::database("rehash_source").await;
+        let plain = HashScheme::new(HashAlgorithm::Blake3);
+        crate::postgres::init_with(&source, plain).await.unwrap();
+        let target = testing::database("rehash_target").await;
+        crate::postgres::init(&target).await.unwrap();
+        let mut source = source.acquire().await.unwrap();
+        let mut target = target.acquire().await.unwrap();
+
+        let content = odb::write_content(b"a", plain, &mut *source).await.unwrap();
+        let mut register = RegisterData::new();
+        register.insert(
+            RegisterEntryKey::try_from("a".to_string()).unwrap(),
+            content,
+        );
+        let register = odb::write(&register, plain, &mut *source).await.unwrap();
+        let root = CommitData::root(plain).unwrap();
+        let (parent, _) = root.hash_with(plain).unwrap();
+        let commit = CommitData::new(
+            register,
+            parent,
+            None,
+            None,
+            root.saves(),
+            OffsetDateTime::UNIX_EPOCH,
+            Identity::new("alice", ""),
+            "first",
+            "",
+        );
+        let commit = odb::write(&commit, plain, &mut *source).await.unwrap();
+        let refs = [Ref {
+            name: "refs/heads/main".into(),
+            oid: commit,
+        }];
+
+        // content that doesn't match its old oid is never copied
+        let mut tampered = Contents(HashMap::from([(content, b"b".to_vec())]));
+        let mut copied = Contents(HashMap::new());
+        let result =
+            super::rehash(&mut source, &mut target, &refs, &mut tampered, &mut copied).await;
+        assert!(matches!(result, Err(Error::HashMismatch { expected, .. }) if expected == content));
+        assert!(copied.0.is_empty());
+
+        let mut contents = Contents(HashMap::from([(content, b"a".to_vec())]));
+        let rehashed = super::rehash(&mut source, &mut target, &refs, &mut contents, &mut copied)
+            .await
+            .unwrap();
+        let scheme = HashScheme::default();
+        assert_ne!(rehashed[0].oid, commit);
+        assert_eq!(
+            odb::resolve_legacy(commit, &mut *target).await.unwrap(),
+            Some(rehashed[0].oid)
+        );
+        odb::verified::get_commit(rehashed[0].oid, scheme, &mut *target)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(copied.0[&crate::content_oid(scheme, b"a")], b"a");
+    }
+}
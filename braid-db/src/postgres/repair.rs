@@ -0,0 +1,275 @@
+//! Data repairs that migrations run after their SQL, for data only the
+//! canonical encodings of objects can derive.
+
+use braid_hash::{HashScheme, Oid};
+use sqlx::PgConnection;
+
+use crate::{bytes::Hash, postgres::odb, Error, Object, ObjectData, ObjectKind, Result};
+
+/// Objects are repaired in batches of this size.
+const BATCH_SIZE: i64 = 1000;
+
+/// Backfills the canonical encoding of objects written before encodings were
+/// stored, from their relational rows. An object whose rows no longer hash to
+/// its oid is left without encoding and returned. Once every object has its
+/// encoding, `braid.object` is checked to keep it that way.
+pub(super) async fn object_encodings(conn: &mut PgConnection) -> Result<Vec<Oid>> {
+    let exists: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT FROM braid.object WHERE kind != 'content' AND encoded IS NULL)",
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    let mut unrepairable = Vec::new();
+    if exists {
+        let scheme = super::hash_scheme(&mut *conn).await?;
+        let mut after = Oid::ZERO;
+
+        loop {
+            let batch: Vec<(Oid, ObjectKind)> = sqlx::query_as(
+                "SELECT id, kind
+                FROM braid.object
+                WHERE kind != 'content' AND encoded IS NULL AND id > $1
+                ORDER BY id
+                LIMIT $2",
+            )
+            .bind(after)
+            .bind(BATCH_SIZE)
+            .fetch_all(&mut *conn)
+            .await?;
+
+            let Some((last, _)) = batch.last() else {
+                break;
+            };
+            after = *last;
+
+            let mut ids = Vec::with_capacity(batch.len());
+            let mut encodings = Vec::with_capacity(batch.len());
+            for (id, kind) in batch {
+                match encode(id, kind, scheme, &mut *conn).await? {
+                    Some((oid, encoded)) if oid == id => {
+                        ids.push(id);
+                        encodings.push(encoded);
+                    }
+                    _ => unrepairable.push(id),
+                }
+            }
+
+            sqlx::query(
+                "UPDATE braid.object AS o
+                SET encoded = e.encoded
+                FROM UNNEST($1::bytea[], $2::bytea[]) AS e(id, encoded)
+                WHERE o.id = e.id",
+            )
+            .bind(ids)
+            .bind(encodings)
+            .execute(&mut *conn)
+            .await?;
+        }
+    }
+
+    if unrepairable.is_empty() {
+        sqlx::query("ALTER TABLE braid.object VALIDATE CONSTRAINT object_encoded")
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(unrepairable)
+}
+
+/// Encodes an object from its relational rows.
+async fn encode(
+    oid: Oid,
+    kind: ObjectKind,
+    scheme: HashScheme,
+    conn: &mut PgConnection,
+) -> Result<Option<(Oid, Vec<u8>)>> {
+    let encoded = match kind {
+        ObjectKind::Content => return Err(Error::UnencodedKind(kind)),
+        ObjectKind::Commit => match odb::get_commit(oid, conn).await? {
+            Some(commit) => commit.data().hash_with(scheme)?,
+            None => return Ok(None),
+        },
+        ObjectKind::Save => match odb::get_save(oid, conn).await? {
+            Some(save) => save.data().hash_with(scheme)?,
+            None => return Ok(None),
+        },
+        ObjectKind::Register => match odb::get_register(oid, conn).await? {
+            Some(register) => register.data().hash_with(scheme)?,
+            None => return Ok(None),
+        },
+        ObjectKind::SaveRegister => match odb::get_save_register(oid, conn).await? {
+            Some(register) => register.data().hash_with(scheme)?,
+            None => return Ok(None),
+        },
+    };
+    Ok(Some(encoded))
+}
+
+/// Re-derives the entries of every register from its encoding, returning the
+/// registers that can't be. Each register is verified against its oid, before
+/// and after, so the repair never writes unverified entries. Registers without
+/// an encoding are left to [`object_encodings`], which reports them as well.
+pub(super) async fn register_entries(conn: &mut PgConnection) -> Result<Vec<Oid>> {
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT FROM braid.register)")
+        .fetch_one(&mut *conn)
+        .await?;
+    if !exists {
+        return Ok(Vec::new());
+    }
+
+    let scheme = super::hash_scheme(&mut *conn).await?;
+    let mut unrepairable = Vec::new();
+    let mut after = Oid::ZERO;
+
+    loop {
+        let batch: Vec<(Oid, Option<Vec<u8>>)> = sqlx::query_as(
+            "SELECT r.id, o.encoded
+            FROM braid.register AS r
+            INNER JOIN braid.object AS o ON o.id = r.id
+            WHERE r.id > $1
+            ORDER BY r.id
+            LIMIT $2",
+        )
+        .bind(after)
+        .bind(BATCH_SIZE)
+        .fetch_all(&mut *conn)
+        .await?;
+
+        let Some((last, _)) = batch.last() else {
+            return Ok(unrepairable);
+        };
+        after = *last;
+
+        for (id, encoded) in batch {
+            let data = match encoded
+                .as_deref()
+                .map(|encoded| Object::decode(encoded, scheme))
+            {
+                Some(Ok((_, ObjectData::Register(data)))) => data,
+                _ => {
+                    unrepairable.push(id);
+                    continue;
+                }
+            };
+            if odb::verified::verify(id, &data, scheme).is_err() {
+                unrepairable.push(id);
+                continue;
+            }
+
+            if let Some(rows) = odb::get_register(id, &mut *conn).await? {
+                if odb::verified::verify(id, rows.data(), scheme).is_ok() {
+                    continue;
+                }
+            }
+
+            // entries that exist are skipped, the others are inserted
+            odb::write(&data, scheme, &mut *conn).await?;
+            if let Some(rows) = odb::get_register(id, &mut *conn).await? {
+                odb::verified::verify(id, rows.data(), scheme)?;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use braid_hash::Oid;
+
+    use crate::{
+        postgres::{
+            fsck::{self, Issue},
+            odb,
+            testing::{self, BASELINE_SCHEME},
+        },
+        register::RegisterData,
+        Error, ObjectKind, RegisterEntryKey,
+    };
+
+    fn register(entries: &[(&str, Oid)]) -> RegisterData<String> {
+        let mut data = RegisterData::new();
+        for (key, oid) in entries {
+            data.insert(RegisterEntryKey::try_from(key.to_string()).unwrap(), *oid);
+        }
+        data
+    }
+
+    #[tokio::test]
+    async fn test_register_entries() {
+        let pool = testing::database("repair_register_entries").await;
+        crate::postgres::init(&pool).await.unwrap();
+        let mut conn = pool.acquire().await.unwrap();
+        let scheme = crate::postgres::hash_scheme(&mut *conn).await.unwrap();
+
+        let content = odb::write_content(b"a", scheme, &mut *conn).await.unwrap();
+        let first = odb::write(&register(&[("a", content)]), scheme, &mut *conn)
+            .await
+            .unwrap();
+        let second = register(&[("a", content), ("b", content)]);
+        let second = odb::write(&second, scheme, &mut *conn).await.unwrap();
+
+        sqlx::query("DELETE FROM braid.register_entry WHERE register = $1 AND key = 'a'")
+            .bind(second)
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+
+        // an encoding that doesn't hash to the oid is never trusted
+        sqlx::query("UPDATE braid.object SET encoded = (SELECT encoded FROM braid.object WHERE id = $1) WHERE id = $2")
+            .bind(second)
+            .bind(first)
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+
+        let unrepairable = super::register_entries(&mut conn).await.unwrap();
+        assert_eq!(unrepairable, vec![first]);
+        let rows = odb::get_register(second, &mut *conn)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(rows.data().len(), 2);
+        assert!(matches!(
+            odb::verified::get_register(first, scheme, &mut *conn).await,
+            Err(Error::HashMismatch { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_unrepairable_baseline() {
+        let pool = testing::baseline("repair_unrepairable_baseline").await;
+        let mut conn = pool.acquire().await.unwrap();
+
+        let scheme = BASELINE_SCHEME;
+        let content = crate::content_oid(scheme, b"a");
+        sqlx::query("CALL braid.create_content($1)")
+            .bind(content)
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        let first = testing::baseline_register(&register(&[("a", content)]), &mut conn).await;
+        let second = register(&[("a", content), ("b", content)]);
+        let second = testing::baseline_register(&second, &mut conn).await;
+
+        // the second register lost its entry `a` before encodings were stored
+        let report = crate::postgres::migrate(&pool).await.unwrap();
+        assert_eq!(report.unrepairable, vec![second]);
+
+        odb::verified::get_register(first, scheme, &mut *conn)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(matches!(
+            odb::read_raw(second, &mut *conn).await,
+            Err(Error::UnencodedKind(ObjectKind::Register))
+        ));
+
+        let report = fsck::verify(&mut conn, []).await.unwrap();
+        assert_eq!(
+            report.issues,
+            [Issue::MissingEncoding {
+                kind: ObjectKind::Register,
+                oid: second,
+            }]
+        );
+    }
+}
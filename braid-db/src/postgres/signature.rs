@@ -0,0 +1,155 @@
+use braid_hash::{HashScheme, Oid};
+use sqlx::PgConnection;
+
+use crate::{
+    signing::{Signature, SignaturePolicy},
+    Error, ObjectKind, Result,
+};
+
+use super::odb;
+
+/// Stores a signature for a commit or save after checking it against the
+/// object's canonical encoding, which must hash to `oid` under `scheme`.
+pub async fn add(
+    oid: Oid,
+    signature: &Signature,
+    scheme: HashScheme,
+    conn: &mut PgConnection,
+) -> Result<()> {
+    let encoded = read_signable(oid, scheme, conn).await?;
+    signature.verify_encoded(&encoded)?;
+
+    sqlx::query(
+        "INSERT INTO braid.signature (object, public_key, signature)
+        VALUES ($1, $2, $3)
+        ON CONFLICT DO NOTHING",
+    )
+    .bind(oid)
+    .bind(signature.key().as_bytes().as_slice())
+    .bind(signature.to_bytes().as_slice())
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Reads the signatures of an object, verifying each of them.
+pub async fn get(oid: Oid, scheme: HashScheme, conn: &mut PgConnection) -> Result<Vec<Signature>> {
+    let rows: Vec<(Vec<u8>, Vec<u8>)> = sqlx::query_as(
+        "SELECT public_key, signature FROM braid.signature WHERE object = $1 ORDER BY public_key",
+    )
+    .bind(oid)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    if rows.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let encoded = read_signable(oid, scheme, conn).await?;
+    rows.into_iter()
+        .map(|(key, signature)| {
+            let key = key.try_into().map_err(|_| Error::InvalidSignature)?;
+            let signature = signature.try_into().map_err(|_| Error::InvalidSignature)?;
+            let signature = Signature::from_bytes(&key, &signature)?;
+            signature.verify_encoded(&encoded)?;
+            Ok(signature)
+        })
+        .collect()
+}
+
+/// Checks `policy` for an update of `refname` to commit `oid`.
+pub async fn check_ref_update(
+    policy: &SignaturePolicy,
+    refname: &str,
+    oid: Oid,
+    scheme: HashScheme,
+    conn: &mut PgConnection,
+) -> Result<()> {
+    if !policy.is_protected(refname) {
+        return Ok(());
+    }
+
+    odb::validate::<crate::oid::CommitOid>(oid, &mut *conn).await?;
+    let signatures = get(oid, scheme, conn).await?;
+    policy.check(refname, oid, &signatures)
+}
+
+async fn read_signable(oid: Oid, scheme: HashScheme, conn: &mut PgConnection) -> Result<Vec<u8>> {
+    match odb::verified::read_raw(oid, scheme, conn).await? {
+        Some((ObjectKind::Commit | ObjectKind::Save, encoded)) => Ok(encoded),
+        Some((kind, _)) => Err(Error::UnexpectedKind {
+            expected: ObjectKind::Commit,
+            actual: kind,
+        }),
+        None => Err(Error::MissingReference { oid, kind: None }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        commit::{CommitData, Identity},
+        postgres::{odb, testing},
+        signing::{self, SignaturePolicy, SigningKey},
+        Error, Hash,
+    };
+
+    #[tokio::test]
+    async fn test_signatures() {
+        let pool = testing::database("signature").await;
+        crate::postgres::init(&pool).await.unwrap();
+        let mut conn = pool.acquire().await.unwrap();
+        let scheme = crate::postgres::hash_scheme(&mut *conn).await.unwrap();
+
+        let root = CommitData::root(scheme).unwrap();
+        let (root_id, _) = root.hash_with(scheme).unwrap();
+        let data = CommitData::new(
+            root.register(),
+            root_id,
+            None,
+            None,
+            root.saves(),
+            root.date(),
+            Identity::new("Ada", "ada@example.com"),
+            "signed",
+            "",
+        );
+        let oid = odb::write(&data, scheme, &mut *conn).await.unwrap();
+
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let (_, signature) = signing::sign(&data, scheme, &key).unwrap();
+        super::add(oid, &signature, scheme, &mut conn)
+            .await
+            .unwrap();
+        let signatures = super::get(oid, scheme, &mut conn).await.unwrap();
+        assert_eq!(signatures, std::slice::from_ref(&signature));
+
+        let policy = SignaturePolicy::new()
+            .protect("refs/heads/main")
+            .trust(key.verifying_key());
+        super::check_ref_update(&policy, "refs/heads/main", oid, scheme, &mut conn)
+            .await
+            .unwrap();
+        assert!(matches!(
+            super::check_ref_update(&policy, "refs/heads/main", root_id, scheme, &mut conn).await,
+            Err(Error::UnsignedCommit { .. })
+        ));
+
+        // a tampered encoding is never signed or accepted
+        sqlx::query("UPDATE braid.object SET encoded = $1 WHERE id = $2")
+            .bind(root.hash_with(scheme).unwrap().1)
+            .bind(oid)
+            .execute(&mut *conn)
+            .await
+            .unwrap();
+        assert!(matches!(
+            super::add(oid, &signature, scheme, &mut conn).await,
+            Err(Error::HashMismatch { .. })
+        ));
+        assert!(matches!(
+            super::check_ref_update(&policy, "refs/heads/main", oid, scheme, &mut conn).await,
+            Err(Error::HashMismatch { .. })
+        ));
+    }
+}
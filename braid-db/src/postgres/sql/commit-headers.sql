@@ -0,0 +1,73 @@
+CREATE TYPE braid.header_record AS (
+    key varchar(255),
+    value text
+);
+
+CREATE DOMAIN braid.header_records AS braid.header_record[];
+
+-- extension headers of a commit, in the order they are encoded
+CREATE TABLE braid.commit_header (
+    commit bytea NOT NULL,
+    position integer NOT NULL,
+    key varchar(255) NOT NULL,
+    value text NOT NULL,
+
+    PRIMARY KEY (commit, position),
+
+    FOREIGN KEY (commit) REFERENCES braid.commit (id)
+);
+
+CREATE INDEX commit_header_key_value ON braid.commit_header (key, value);
+
+DROP PROCEDURE braid.create_commit(bytea, bytea, bytea, bytea, bytea, bytea, timestamp with time zone, varchar,
+    varchar, varchar, varchar, timestamp with time zone, text, text, bytea);
+DROP FUNCTION braid.get_commit(bytea);
+
+-- `odb/bulk.rs` inserts the same rows for its batches, keep both in sync
+CREATE PROCEDURE braid.create_commit(id bytea, register bytea, parent bytea, merge_parent bytea, rebase_of bytea,
+    saves bytea, date timestamp with time zone, committer varchar(255), committer_email varchar(255),
+    author varchar(255), author_email varchar(255), author_date timestamp with time zone,
+    summary text, body text, headers braid.header_records, encoded bytea) AS $$
+BEGIN
+    CALL braid.create_object(id, 'commit', encoded);
+
+    INSERT INTO braid.save_parent (id, is_commit)
+    VALUES (id, TRUE)
+    ON CONFLICT DO NOTHING;
+
+    INSERT INTO braid.commit (id, register, parent, merge_parent, rebase_of, saves, date, committer, committer_email,
+        author, author_email, author_date, summary, body)
+    VALUES (id, register, parent, merge_parent, rebase_of, saves, date, committer, committer_email,
+        author, author_email, author_date, summary, body)
+    ON CONFLICT DO NOTHING;
+
+    INSERT INTO braid.commit_header (commit, position, key, value)
+    SELECT id, h.position - 1, h.key, h.value
+    FROM UNNEST(headers) WITH ORDINALITY AS h(key, value, position)
+    ON CONFLICT DO NOTHING;
+END $$ LANGUAGE plpgsql;
+
+CREATE FUNCTION braid.get_commit(commit_id bytea)
+RETURNS TABLE(id bytea, register bytea, parent bytea, merge_parent bytea, rebase_of bytea,
+    saves bytea, date timestamp with time zone, committer varchar(255), committer_email varchar(255),
+    author varchar(255), author_email varchar(255), author_date timestamp with time zone, summary text, body text,
+    header_keys text[], header_values text[]) AS $$
+BEGIN
+    RETURN QUERY
+    SELECT c.id, c.register, c.parent, c.merge_parent, c.rebase_of, c.saves, c.date, c.committer, c.committer_email,
+        c.author, c.author_email, c.author_date, c.summary, c.body,
+        ARRAY(SELECT h.key::text FROM braid.commit_header AS h WHERE h.commit = c.id ORDER BY h.position),
+        ARRAY(SELECT h.value FROM braid.commit_header AS h WHERE h.commit = c.id ORDER BY h.position)
+    FROM braid.commit as c
+    WHERE c.id = commit_id;
+END $$ LANGUAGE plpgsql;
+
+CREATE FUNCTION braid.find_commits_by_header(header_key varchar(255), header_value text)
+RETURNS TABLE(id bytea) AS $$
+BEGIN
+    RETURN QUERY
+    SELECT DISTINCT h.commit
+    FROM braid.commit_header AS h
+    WHERE h.key = header_key AND (header_value IS NULL OR h.value = header_value)
+    ORDER BY h.commit;
+END $$ LANGUAGE plpgsql;
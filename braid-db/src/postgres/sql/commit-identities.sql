@@ -0,0 +1,50 @@
+-- commits written before were authored by their committer, without emails,
+-- which is what their format version 0 encoding holds
+ALTER TABLE braid.commit
+    ADD COLUMN committer_email varchar(255),
+    ADD COLUMN author varchar(255),
+    ADD COLUMN author_email varchar(255),
+    ADD COLUMN author_date timestamp with time zone;
+
+UPDATE braid.commit
+SET committer_email = '', author = committer, author_email = '', author_date = date;
+
+ALTER TABLE braid.commit
+    ALTER COLUMN committer_email SET NOT NULL,
+    ALTER COLUMN author SET NOT NULL,
+    ALTER COLUMN author_email SET NOT NULL,
+    ALTER COLUMN author_date SET NOT NULL;
+
+DROP PROCEDURE braid.create_commit(bytea, bytea, bytea, bytea, bytea, bytea, timestamp with time zone, varchar,
+    text, text, bytea);
+DROP FUNCTION braid.get_commit(bytea);
+
+CREATE PROCEDURE braid.create_commit(id bytea, register bytea, parent bytea, merge_parent bytea, rebase_of bytea,
+    saves bytea, date timestamp with time zone, committer varchar(255), committer_email varchar(255),
+    author varchar(255), author_email varchar(255), author_date timestamp with time zone,
+    summary text, body text, encoded bytea) AS $$
+BEGIN
+    CALL braid.create_object(id, 'commit', encoded);
+
+    INSERT INTO braid.save_parent (id, is_commit)
+    VALUES (id, TRUE)
+    ON CONFLICT DO NOTHING;
+
+    INSERT INTO braid.commit (id, register, parent, merge_parent, rebase_of, saves, date, committer, committer_email,
+        author, author_email, author_date, summary, body)
+    VALUES (id, register, parent, merge_parent, rebase_of, saves, date, committer, committer_email,
+        author, author_email, author_date, summary, body)
+    ON CONFLICT DO NOTHING;
+END $$ LANGUAGE plpgsql;
+
+CREATE FUNCTION braid.get_commit(commit_id bytea)
+RETURNS TABLE(id bytea, register bytea, parent bytea, merge_parent bytea, rebase_of bytea,
+    saves bytea, date timestamp with time zone, committer varchar(255), committer_email varchar(255),
+    author varchar(255), author_email varchar(255), author_date timestamp with time zone, summary text, body text) AS $$
+BEGIN
+    RETURN QUERY
+    SELECT c.id, c.register, c.parent, c.merge_parent, c.rebase_of, c.saves, c.date, c.committer, c.committer_email,
+        c.author, c.author_email, c.author_date, c.summary, c.body
+    FROM braid.commit as c
+    WHERE c.id = commit_id;
+END $$ LANGUAGE plpgsql;
@@ -0,0 +1,20 @@
+-- the plain BLAKE3 hash of content, which verified streams of its bytes are
+-- checked against, see `braid_hash::stream`. NULL for content recorded before,
+-- whose bytes the database never saw.
+ALTER TABLE braid.content ADD COLUMN stream_root bytea;
+ALTER TABLE braid.content ADD CONSTRAINT content_stream_root
+    CHECK (octet_length(stream_root) = 32);
+
+DROP PROCEDURE braid.create_content(bytea);
+
+-- content recorded again gets the stream root it was missing. `odb/bulk.rs`
+-- inserts the same rows for its batches, keep both in sync
+CREATE PROCEDURE braid.create_content(id bytea, stream_root bytea) AS $$
+BEGIN
+    CALL braid.create_object(id, 'content', NULL);
+
+    INSERT INTO braid.content (id, stream_root)
+    VALUES (id, stream_root)
+    ON CONFLICT ON CONSTRAINT content_pkey
+    DO UPDATE SET stream_root = coalesce(braid.content.stream_root, EXCLUDED.stream_root);
+END $$ LANGUAGE plpgsql;
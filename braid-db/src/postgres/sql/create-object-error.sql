@@ -0,0 +1,22 @@
+-- the baseline message referenced undefined variables and failed instead of
+-- raising, see `err::map_postgres_error` for how BR001 is reported
+-- raises SQLSTATE BR001 with DETAIL `<hex id>:<existing kind>:<requested kind>`
+CREATE OR REPLACE PROCEDURE braid.create_object(object_id bytea, object_kind braid.object_kind) AS $$
+DECLARE
+    inserted bytea;
+    existing braid.object_kind;
+BEGIN
+    INSERT INTO braid.object (id, kind)
+    VALUES (object_id, object_kind)
+    ON CONFLICT DO NOTHING
+    RETURNING id INTO inserted;
+
+    IF inserted IS NULL THEN
+        SELECT o.kind INTO existing FROM braid.object o WHERE o.id = object_id;
+
+        IF existing != object_kind THEN
+            RAISE EXCEPTION 'Object with id % already exists and is not a %', encode(object_id, 'hex'), object_kind
+            USING ERRCODE = 'BR001', DETAIL = format('%s:%s:%s', encode(object_id, 'hex'), existing, object_kind);
+        END IF;
+    END IF;
+END $$ LANGUAGE plpgsql;
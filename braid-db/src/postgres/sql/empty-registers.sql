@@ -0,0 +1,20 @@
+-- registers without entries returned no rows and read as missing
+CREATE OR REPLACE FUNCTION braid.get_register(register_id bytea)
+RETURNS TABLE(key varchar(255), content bytea) AS $$
+BEGIN
+    RETURN QUERY
+    SELECT re.key, re.content
+    FROM braid.register AS r
+    LEFT JOIN braid.register_entry AS re ON re.register = r.id
+    WHERE r.id = register_id;
+END $$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION braid.get_save_register(save_register_id bytea)
+RETURNS TABLE(key varchar(255), content bytea) AS $$
+BEGIN
+    RETURN QUERY
+    SELECT sre.key, sre.save
+    FROM braid.save_register AS sr
+    LEFT JOIN braid.save_register_entry AS sre ON sre.save_register = sr.id
+    WHERE sr.id = save_register_id;
+END $$ LANGUAGE plpgsql;
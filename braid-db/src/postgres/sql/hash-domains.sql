@@ -0,0 +1,14 @@
+-- whether each kind of object is hashed in its own domain, repositories
+-- created before were not
+ALTER TABLE braid.repository ADD COLUMN hash_domains boolean NOT NULL DEFAULT FALSE;
+ALTER TABLE braid.repository ALTER COLUMN hash_domains DROP DEFAULT;
+
+-- oids objects had before the repository was rehashed with a new scheme
+CREATE TABLE braid.legacy_oid (
+    legacy bytea PRIMARY KEY,
+    id bytea NOT NULL,
+
+    CHECK (octet_length(legacy) = 32),
+
+    FOREIGN KEY (id) REFERENCES braid.object (id)
+);
@@ -0,0 +1,111 @@
+-- the canonical encoding that hashes to `id`, content is stored elsewhere.
+-- objects written before are backfilled by `repair::object_encodings`, which
+-- validates the constraint once every object has its encoding
+ALTER TABLE braid.object ADD COLUMN encoded bytea;
+ALTER TABLE braid.object ADD CONSTRAINT object_encoded
+    CHECK ((kind = 'content') = (encoded IS NULL)) NOT VALID;
+
+DROP PROCEDURE braid.create_object(bytea, braid.object_kind);
+DROP PROCEDURE braid.create_register(bytea, braid.entry_records);
+DROP PROCEDURE braid.create_save(bytea, varchar, timestamp with time zone, bytea, bytea);
+DROP PROCEDURE braid.create_save_register(bytea, braid.entry_records);
+DROP PROCEDURE braid.create_commit(bytea, bytea, bytea, bytea, bytea, bytea, timestamp with time zone, varchar,
+    text, text);
+
+-- raises SQLSTATE BR001 with DETAIL `<hex id>:<existing kind>:<requested kind>`.
+-- `odb/bulk.rs` checks kinds the same way for its batches, keep both in sync
+CREATE PROCEDURE braid.create_object(object_id bytea, object_kind braid.object_kind, object_encoded bytea) AS $$
+DECLARE
+    inserted bytea;
+    existing braid.object_kind;
+BEGIN
+    INSERT INTO braid.object (id, kind, encoded)
+    VALUES (object_id, object_kind, object_encoded)
+    ON CONFLICT DO NOTHING
+    RETURNING id INTO inserted;
+
+    IF inserted IS NULL THEN
+        SELECT o.kind INTO existing FROM braid.object o WHERE o.id = object_id;
+
+        IF existing != object_kind THEN
+            RAISE EXCEPTION 'Object with id % already exists and is not a %', encode(object_id, 'hex'), object_kind
+            USING ERRCODE = 'BR001', DETAIL = format('%s:%s:%s', encode(object_id, 'hex'), existing, object_kind);
+        END IF;
+    END IF;
+END $$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE PROCEDURE braid.create_content(id bytea) AS $$
+BEGIN
+    CALL braid.create_object(id, 'content', NULL);
+
+    INSERT INTO braid.content (id)
+    VALUES (id);
+END $$ LANGUAGE plpgsql;
+
+-- `odb/bulk.rs` inserts the same rows for its batches, keep both in sync
+CREATE PROCEDURE braid.create_register(id bytea, entries braid.entry_records, encoded bytea) AS $$
+BEGIN
+    CALL braid.create_object(id, 'register', encoded);
+
+    INSERT INTO braid.register (id)
+    VALUES (id)
+    ON CONFLICT DO NOTHING;
+
+    INSERT INTO braid.register_entry (register, key, content)
+    SELECT id, e.key, e.content
+    FROM UNNEST(entries) AS e
+    ON CONFLICT DO NOTHING;
+END $$ LANGUAGE plpgsql;
+
+-- `odb/bulk.rs` inserts the same rows for its batches, keep both in sync
+CREATE PROCEDURE braid.create_save(id bytea, author varchar(255), date timestamp with time zone, content bytea, parent bytea,
+    encoded bytea) AS $$
+BEGIN
+    CALL braid.create_object(id, 'save', encoded);
+
+    INSERT INTO braid.save_parent (id, is_commit)
+    VALUES (id, FALSE)
+    ON CONFLICT DO NOTHING;
+
+    INSERT INTO braid.save (id, author, date, content, parent)
+    VALUES (id, author, date, content, parent)
+    ON CONFLICT DO NOTHING;
+END $$ LANGUAGE plpgsql;
+
+-- `odb/bulk.rs` inserts the same rows for its batches, keep both in sync
+CREATE PROCEDURE braid.create_save_register(id bytea, entries braid.entry_records, encoded bytea) AS $$
+BEGIN
+    CALL braid.create_object(id, 'save_register', encoded);
+
+    INSERT INTO braid.save_register (id)
+    VALUES (id)
+    ON CONFLICT DO NOTHING;
+
+    INSERT INTO braid.save_register_entry (save_register, key, save)
+    SELECT id, e.key, e.content
+    FROM UNNEST(entries) AS e
+    ON CONFLICT DO NOTHING;
+END $$ LANGUAGE plpgsql;
+
+CREATE PROCEDURE braid.create_commit(id bytea, register bytea, parent bytea, merge_parent bytea, rebase_of bytea,
+    saves bytea, date timestamp with time zone, committer varchar(255), summary text, body text, encoded bytea) AS $$
+BEGIN
+    CALL braid.create_object(id, 'commit', encoded);
+
+    INSERT INTO braid.save_parent (id, is_commit)
+    VALUES (id, TRUE)
+    ON CONFLICT DO NOTHING;
+
+    INSERT INTO braid.commit (id, register, parent, merge_parent, rebase_of, saves, date, committer, summary, body)
+    VALUES (id, register, parent, merge_parent, rebase_of, saves, date, committer, summary, body)
+    ON CONFLICT DO NOTHING;
+END $$ LANGUAGE plpgsql;
+
+CREATE FUNCTION braid.read_raw(object_id bytea)
+RETURNS TABLE(kind braid.object_kind, encoded bytea) AS $$
+BEGIN
+    RETURN QUERY
+    SELECT o.kind, o.encoded
+    FROM braid.object AS o
+    WHERE o.id = object_id;
+END $$ LANGUAGE plpgsql;
@@ -0,0 +1,4 @@
+-- register entries were keyed by (key, content), so a register lost every entry
+-- it shared with a register written before it, see `repair::register_entries`
+ALTER TABLE braid.register_entry DROP CONSTRAINT register_entry_pkey;
+ALTER TABLE braid.register_entry ADD PRIMARY KEY (register, key);
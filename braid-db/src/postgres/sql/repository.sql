@@ -0,0 +1,16 @@
+-- REPOSITORY METADATA
+-- every hash algorithm produces 32 bytes, as `braid.object` checks for its ids
+CREATE TABLE braid.repository (
+    -- a single row
+    id boolean PRIMARY KEY DEFAULT TRUE,
+    hash_algorithm varchar(16) NOT NULL,
+
+    CHECK (id),
+    CHECK (hash_algorithm IN ('blake3', 'sha256'))
+);
+
+-- repositories with objects were initialized before algorithms existed, the
+-- others get their row from `init`
+INSERT INTO braid.repository (hash_algorithm)
+SELECT 'blake3'
+WHERE EXISTS (SELECT FROM braid.object);
@@ -0,0 +1,13 @@
+-- signatures over the canonical encoding, not covered by the object's id
+CREATE TABLE braid.signature (
+    object bytea NOT NULL,
+    public_key bytea NOT NULL,
+    signature bytea NOT NULL,
+
+    PRIMARY KEY (object, public_key),
+
+    FOREIGN KEY (object) REFERENCES braid.object (id),
+
+    CHECK (octet_length(public_key) = 32),
+    CHECK (octet_length(signature) = 64)
+);
@@ -0,0 +1,125 @@
+//! Scratch databases for tests, on the server the `POSTGRES_*` variables of
+//! the dev container point to.
+
+use braid_hash::{HashAlgorithm, HashScheme, Oid};
+use sqlx::{postgres::PgConnectOptions, Connection, Executor, PgConnection, PgPool};
+
+use crate::{bytes::Hash, commit::CommitData, register::RegisterData};
+
+/// How repositories created before migrations existed hashed.
+pub(crate) const BASELINE_SCHEME: HashScheme = HashScheme::new(HashAlgorithm::Blake3);
+
+fn var(name: &str, default: &str) -> String {
+    std::env::var(name).unwrap_or_else(|_| default.to_string())
+}
+
+/// Creates the empty database `braid_test_<name>`, replacing one left by an
+/// earlier run.
+pub(crate) async fn database(name: &str) -> PgPool {
+    let options = PgConnectOptions::new()
+        .host(&var("POSTGRES_HOSTNAME", "localhost"))
+        .port(var("POSTGRES_PORT", "5432").parse().unwrap())
+        .username(&var("POSTGRES_USER", "postgres"))
+        .password(&var("POSTGRES_PASSWORD", "postgres"));
+
+    let name = format!("braid_test_{name}");
+    let mut conn =
+        PgConnection::connect_with(&options.clone().database(&var("POSTGRES_DB", "postgres")))
+            .await
+            .unwrap();
+    conn.execute(format!("DROP DATABASE IF EXISTS {name} WITH (FORCE)").as_str())
+        .await
+        .unwrap();
+    conn.execute(format!("CREATE DATABASE {name}").as_str())
+        .await
+        .unwrap();
+
+    PgPool::connect_with(options.database(&name)).await.unwrap()
+}
+
+/// Creates a database the way `init` did before migrations existed, with the
+/// empty registers and the root commit.
+pub(crate) async fn baseline(name: &str) -> PgPool {
+    let pool = database(name).await;
+    let mut conn = pool.acquire().await.unwrap();
+
+    conn.execute("CREATE SCHEMA braid;").await.unwrap();
+    conn.execute(include_str!("sql/init-braid.sql"))
+        .await
+        .unwrap();
+    conn.execute("CALL braid.init_braid();").await.unwrap();
+    conn.execute("DROP PROCEDURE braid.init_braid;")
+        .await
+        .unwrap();
+
+    let (register, _) = RegisterData::<String>::new()
+        .hash_with(BASELINE_SCHEME)
+        .unwrap();
+    sqlx::query("CALL braid.create_register($1, ARRAY[]::braid.entry_records)")
+        .bind(register)
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+    let (saves, _) = crate::register::SaveRegisterData::<String>::new()
+        .hash_with(BASELINE_SCHEME)
+        .unwrap();
+    sqlx::query("CALL braid.create_save_register($1, ARRAY[]::braid.entry_records)")
+        .bind(saves)
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+    baseline_commit(&CommitData::root(BASELINE_SCHEME).unwrap(), &mut conn).await;
+
+    pool
+}
+
+/// Writes a register like the procedure of the baseline schema, which drops
+/// entries another register already has.
+pub(crate) async fn baseline_register(data: &RegisterData<String>, conn: &mut PgConnection) -> Oid {
+    let (oid, _) = data.hash_with(BASELINE_SCHEME).unwrap();
+    let (keys, contents): (Vec<&str>, Vec<Oid>) =
+        data.iter().map(|(key, oid)| (key.as_str(), *oid)).unzip();
+
+    sqlx::query("CALL braid.create_register($1, ARRAY[]::braid.entry_records)")
+        .bind(oid)
+        .execute(&mut *conn)
+        .await
+        .unwrap();
+    sqlx::query(
+        "INSERT INTO braid.register_entry (register, key, content)
+        SELECT $1, e.key, e.content
+        FROM UNNEST($2::varchar[], $3::bytea[]) AS e(key, content)
+        ON CONFLICT DO NOTHING",
+    )
+    .bind(oid)
+    .bind(keys)
+    .bind(contents)
+    .execute(conn)
+    .await
+    .unwrap();
+    oid
+}
+
+/// Writes a commit with the procedure of the baseline schema, which only has
+/// the fields of format version 0.
+pub(crate) async fn baseline_commit<S: AsRef<str>>(
+    data: &CommitData<S>,
+    conn: &mut PgConnection,
+) -> Oid {
+    let (oid, _) = data.hash_with(BASELINE_SCHEME).unwrap();
+    sqlx::query("CALL braid.create_commit($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)")
+        .bind(oid)
+        .bind(data.register())
+        .bind(data.parent())
+        .bind(data.merge_parent())
+        .bind(data.rebase_of())
+        .bind(data.saves())
+        .bind(data.date())
+        .bind(data.committer().name().as_ref())
+        .bind(data.summary().as_ref())
+        .bind(data.body().as_ref())
+        .execute(conn)
+        .await
+        .unwrap();
+    oid
+}
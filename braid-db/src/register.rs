@@ -2,7 +2,7 @@ use std::{borrow::Borrow, collections::BTreeMap};
 
 use braid_hash::Oid;
 
-use crate::{Key, ObjectKind, RegisterEntryKey, SaveEntryKey};
+use crate::{key::InvalidCharacterInKeyError, Key, ObjectKind, RegisterEntryKey, SaveEntryKey};
 
 pub enum RegisterKind {
     Register,
@@ -19,7 +19,6 @@ impl RegisterKind {
 }
 
 pub trait EntryData<S>: crate::sealed::Sealed {
-    const EMPTY_ID: Oid;
     const REGISTER_KIND: RegisterKind;
     type Key: Key<S>;
 
@@ -31,7 +30,7 @@ pub trait EntryData<S>: crate::sealed::Sealed {
 
     fn insert(&mut self, key: Self::Key, oid: Oid);
 
-    fn iter<'a>(&'a self) -> impl ExactSizeIterator<Item = (&S, &Oid)>
+    fn iter<'a>(&'a self) -> impl ExactSizeIterator<Item = (&'a S, &'a Oid)>
     where
         S: 'a;
 
@@ -40,19 +39,26 @@ pub trait EntryData<S>: crate::sealed::Sealed {
     fn is_empty(&self) -> bool;
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct RegisterData<S>(BTreeMap<S, Oid>);
 
 impl<S> crate::sealed::Sealed for RegisterData<S> {}
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(deserialize = "S: serde::Deserialize<'de> + Ord + AsRef<str>"))
+)]
 pub struct Register<S = String> {
     pub(crate) id: Oid,
     pub(crate) data: RegisterData<S>,
 }
 
 impl Register<()> {
+    /// The id of a register without entries, hashed with the default scheme.
     pub const EMPTY_ID: Oid = Oid::from_bytes([
-        249, 230, 187, 110, 142, 190, 207, 255, 22, 36, 155, 39, 181, 94, 64, 99, 83, 212, 240,
-        161, 116, 63, 80, 246, 150, 238, 139, 55, 60, 211, 211, 141,
+        163, 219, 151, 82, 204, 6, 99, 100, 190, 155, 246, 53, 18, 29, 210, 26, 240, 143, 182, 27,
+        114, 14, 101, 96, 68, 231, 93, 78, 53, 90, 81, 85,
     ]);
 }
 
@@ -66,19 +72,27 @@ impl<S> Register<S> {
     }
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct SaveRegisterData<S>(BTreeMap<S, Oid>);
 
 impl<S> crate::sealed::Sealed for SaveRegisterData<S> {}
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(deserialize = "S: serde::Deserialize<'de> + Ord + AsRef<str>"))
+)]
 pub struct SaveRegister<S = String> {
     pub(crate) id: Oid,
     pub(crate) data: SaveRegisterData<S>,
 }
 
 impl SaveRegister<()> {
+    /// The id of a save register without entries, hashed with the default
+    /// scheme.
     pub const EMPTY_ID: Oid = Oid::from_bytes([
-        170, 108, 88, 58, 52, 10, 245, 194, 182, 224, 232, 252, 161, 20, 33, 183, 207, 7, 140, 128,
-        144, 172, 178, 229, 60, 64, 135, 65, 223, 103, 176, 193,
+        129, 159, 244, 136, 220, 198, 190, 205, 90, 120, 160, 49, 169, 82, 8, 153, 8, 162, 90, 213,
+        249, 148, 67, 213, 104, 46, 125, 223, 117, 206, 19, 31,
     ]);
 }
 
@@ -92,8 +106,86 @@ impl<S> SaveRegister<S> {
     }
 }
 
+/// The entries of a register or save register, borrowed from its encoding.
+///
+/// Created by [`Object::decode_borrowed`](crate::Object::decode_borrowed),
+/// which validates every entry.
+#[derive(Clone, Copy, Debug)]
+pub struct RegisterDataRef<'a> {
+    pub(crate) len: usize,
+    pub(crate) entries: &'a [u8],
+}
+
+impl<'a> RegisterDataRef<'a> {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn iter(&self) -> RegisterEntries<'a> {
+        RegisterEntries {
+            remaining: self.len,
+            entries: self.entries,
+        }
+    }
+}
+
+impl<'a> IntoIterator for RegisterDataRef<'a> {
+    type Item = (&'a str, Oid);
+    type IntoIter = RegisterEntries<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterates the `(key, oid)` entries of a [`RegisterDataRef`] in key order.
+#[derive(Clone, Debug)]
+pub struct RegisterEntries<'a> {
+    remaining: usize,
+    entries: &'a [u8],
+}
+
+impl<'a> Iterator for RegisterEntries<'a> {
+    type Item = (&'a str, Oid);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+
+        let (oid, rest) = self.entries.split_at(Oid::LEN);
+        let oid = Oid::from_bytes(oid.try_into().expect("oid has length Oid::LEN"));
+
+        let len = rest.iter().position(|&b| b == 0)?;
+        let (key, rest) = rest.split_at(len);
+        self.entries = &rest[1..];
+
+        // SAFETY: every key was validated as utf-8 when the register was decoded.
+        let key = unsafe { std::str::from_utf8_unchecked(key) };
+
+        Some((key, oid))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl ExactSizeIterator for RegisterEntries<'_> {}
+
 macro_rules! impl_entry_data {
     ($id:ident::$type:ident<$key:ident> => $kind:ident) => {
+        impl<S: Ord + AsRef<str>> Default for $type<S> {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
         impl<S: Ord + AsRef<str>> $type<S> {
             pub fn new() -> Self {
                 Self(BTreeMap::new())
@@ -110,7 +202,7 @@ macro_rules! impl_entry_data {
                 self.0.insert(key.into_inner(), oid);
             }
 
-            pub fn iter<'a>(&'a self) -> impl ExactSizeIterator<Item = (&S, &Oid)>
+            pub fn iter<'a>(&'a self) -> impl ExactSizeIterator<Item = (&'a S, &'a Oid)>
             where
                 S: 'a,
             {
@@ -124,13 +216,58 @@ macro_rules! impl_entry_data {
             pub fn is_empty(&self) -> bool {
                 self.0.is_empty()
             }
+
+            /// Replaces the oid of every entry with `f(oid)`.
+            #[cfg(feature = "postgres")]
+            pub(crate) fn map_oids(
+                &mut self,
+                mut f: impl FnMut(Oid) -> crate::Result<Oid>,
+            ) -> crate::Result<()> {
+                for oid in self.0.values_mut() {
+                    *oid = f(*oid)?;
+                }
+                Ok(())
+            }
+        }
+
+        impl<S: Ord + AsRef<str>> TryFrom<BTreeMap<S, Oid>> for $type<S> {
+            type Error = InvalidCharacterInKeyError;
+
+            /// Validates every key of `entries`.
+            fn try_from(entries: BTreeMap<S, Oid>) -> Result<Self, Self::Error> {
+                let mut data = Self::new();
+                for (key, oid) in entries {
+                    data.insert($key::try_from(key)?, oid);
+                }
+                Ok(data)
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl<S: serde::Serialize> serde::Serialize for $type<S> {
+            fn serialize<Ser: serde::Serializer>(
+                &self,
+                serializer: Ser,
+            ) -> Result<Ser::Ok, Ser::Error> {
+                self.0.serialize(serializer)
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl<'de, S> serde::Deserialize<'de> for $type<S>
+        where
+            S: serde::Deserialize<'de> + Ord + AsRef<str>,
+        {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let entries = BTreeMap::<S, Oid>::deserialize(deserializer)?;
+                Self::try_from(entries).map_err(serde::de::Error::custom)
+            }
         }
 
         impl<S: AsRef<str>> EntryData<S> for $type<S>
         where
             S: Ord,
         {
-            const EMPTY_ID: Oid = $id::EMPTY_ID;
             const REGISTER_KIND: RegisterKind = RegisterKind::$kind;
             type Key = $key<S>;
 
@@ -149,7 +286,7 @@ macro_rules! impl_entry_data {
                 self.insert(key, oid);
             }
 
-            fn iter<'a>(&'a self) -> impl ExactSizeIterator<Item = (&S, &Oid)>
+            fn iter<'a>(&'a self) -> impl ExactSizeIterator<Item = (&'a S, &'a Oid)>
             where
                 S: 'a,
             {
@@ -169,3 +306,47 @@ macro_rules! impl_entry_data {
 
 impl_entry_data!(Register::RegisterData<RegisterEntryKey> => Register);
 impl_entry_data!(SaveRegister::SaveRegisterData<SaveEntryKey> => SaveRegister);
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use braid_hash::{HashScheme, Oid};
+
+    use super::{Register, RegisterData, SaveRegisterData};
+    use crate::{Hash, RegisterEntryKey};
+
+    #[test]
+    fn test_serde() {
+        let mut data = RegisterData::new();
+        data.insert(
+            RegisterEntryKey::try_from("a".to_string()).unwrap(),
+            Oid::repeat(1),
+        );
+        let (id, _) = data.hash_with(HashScheme::default()).unwrap();
+        let register = Register { id, data };
+
+        let json = serde_json::to_value(&register).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "id": id.to_hex_string(),
+                "data": { "a": Oid::repeat(1).to_hex_string() },
+            })
+        );
+
+        let register: Register = serde_json::from_value(json).unwrap();
+        assert_eq!(register.id(), id);
+        assert_eq!(register.data().get("a"), Some(&Oid::repeat(1)));
+    }
+
+    #[test]
+    fn test_deserialize_validates_keys() {
+        let oid = Oid::ZERO.to_hex_string();
+
+        let json = serde_json::json!({ "a/b": oid });
+        assert!(serde_json::from_value::<RegisterData<String>>(json.clone()).is_err());
+        assert!(serde_json::from_value::<SaveRegisterData<String>>(json).is_ok());
+
+        let json = serde_json::json!({ "a\nb": oid });
+        assert!(serde_json::from_value::<SaveRegisterData<String>>(json).is_err());
+    }
+}
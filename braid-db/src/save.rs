@@ -1,9 +1,13 @@
 use braid_hash::Oid;
 
-#[derive(Clone, Debug)]
+use crate::oid::{ContentOid, SaveParentOid, ValidOid};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "postgres", derive(sqlx::FromRow))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SaveData<S = String> {
     pub(crate) author: S,
+    #[cfg_attr(feature = "serde", serde(with = "time::serde::rfc3339"))]
     pub(crate) date: time::OffsetDateTime,
     pub(crate) content: Oid,
     pub(crate) parent: Oid,
@@ -12,12 +16,7 @@ pub struct SaveData<S = String> {
 impl<S> crate::sealed::Sealed for SaveData<S> {}
 
 impl<S> SaveData<S> {
-    pub fn new(
-        author: S,
-        date: time::OffsetDateTime,
-        content: Oid,
-        parent: Oid,
-    ) -> Self {
+    pub fn new(author: S, date: time::OffsetDateTime, content: Oid, parent: Oid) -> Self {
         Self {
             author,
             date,
@@ -26,6 +25,17 @@ impl<S> SaveData<S> {
         }
     }
 
+    /// Like [`SaveData::new`], but only accepts oids that were validated
+    /// against a store.
+    pub fn from_valid(
+        author: S,
+        date: time::OffsetDateTime,
+        content: ContentOid,
+        parent: impl SaveParentOid,
+    ) -> Self {
+        Self::new(author, date, content.oid(), parent.oid())
+    }
+
     pub fn author(&self) -> &S {
         &self.author
     }
@@ -44,7 +54,18 @@ impl<S> SaveData<S> {
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Save<S = String> {
     pub(crate) id: Oid,
     pub(crate) data: SaveData<S>,
 }
+
+impl<S> Save<S> {
+    pub fn id(&self) -> Oid {
+        self.id
+    }
+
+    pub fn data(&self) -> &SaveData<S> {
+        &self.data
+    }
+}
@@ -0,0 +1,206 @@
+//! Ed25519 signatures for commits and saves.
+//!
+//! A signature covers the canonical encoding of the object, the bytes its oid
+//! is the hash of. Signatures are kept next to the object rather than in its
+//! encoding, so the oid of an object is the same whether it is signed or not
+//! and an object can collect signatures from several keys.
+
+use braid_hash::{HashScheme, Oid};
+use ed25519_dalek::Signer;
+
+pub use ed25519_dalek::{SigningKey, VerifyingKey};
+
+use crate::{commit::CommitData, save::SaveData, Error, Hash, Result};
+
+/// Objects that can be signed.
+pub trait Signable: Hash + crate::sealed::Sealed {}
+
+impl<S: AsRef<str>> Signable for CommitData<S> {}
+impl<S: AsRef<str>> Signable for SaveData<S> {}
+
+/// A signature over an object's canonical encoding, with the key that made it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    pub(crate) key: VerifyingKey,
+    pub(crate) signature: ed25519_dalek::Signature,
+}
+
+impl Signature {
+    pub fn from_bytes(key: &[u8; 32], signature: &[u8; 64]) -> Result<Self> {
+        Ok(Self {
+            key: VerifyingKey::from_bytes(key).map_err(|_| Error::InvalidSignature)?,
+            signature: ed25519_dalek::Signature::from_bytes(signature),
+        })
+    }
+
+    pub fn key(&self) -> &VerifyingKey {
+        &self.key
+    }
+
+    pub fn to_bytes(&self) -> [u8; 64] {
+        self.signature.to_bytes()
+    }
+
+    /// Checks the signature against the canonical encoding of an object.
+    pub fn verify_encoded(&self, encoded: &[u8]) -> Result<()> {
+        self.key
+            .verify_strict(encoded, &self.signature)
+            .map_err(|_| Error::InvalidSignature)
+    }
+}
+
+/// Signs `data` and returns its oid under `scheme` together with the signature.
+pub fn sign(
+    data: &impl Signable,
+    scheme: HashScheme,
+    key: &SigningKey,
+) -> Result<(Oid, Signature)> {
+    let (oid, encoded) = data.hash_with(scheme)?;
+    let signature = Signature {
+        key: key.verifying_key(),
+        signature: key.sign(&encoded),
+    };
+    Ok((oid, signature))
+}
+
+/// Checks that `signature` was made over `data` and returns its oid under
+/// `scheme`.
+pub fn verify(data: &impl Signable, scheme: HashScheme, signature: &Signature) -> Result<Oid> {
+    let (oid, encoded) = data.hash_with(scheme)?;
+    signature.verify_encoded(&encoded)?;
+    Ok(oid)
+}
+
+/// Decides which refs may only point at commits signed by a trusted key.
+///
+/// A ref is protected when its name starts with one of the protected
+/// prefixes, compared by whole path components: `refs/heads/main` protects
+/// `refs/heads/main/fix` but not `refs/heads/main2`. Updates of a protected
+/// ref fail while no key is trusted.
+#[derive(Debug, Clone, Default)]
+pub struct SignaturePolicy {
+    protected: Vec<String>,
+    trusted: Vec<VerifyingKey>,
+}
+
+impl SignaturePolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn protect(mut self, prefix: impl Into<String>) -> Self {
+        self.protected.push(prefix.into());
+        self
+    }
+
+    pub fn trust(mut self, key: VerifyingKey) -> Self {
+        self.trusted.push(key);
+        self
+    }
+
+    pub fn is_protected(&self, refname: &str) -> bool {
+        self.protected.iter().any(|prefix| {
+            match refname.strip_prefix(prefix.trim_end_matches('/')) {
+                Some(rest) => rest.is_empty() || rest.starts_with('/'),
+                None => false,
+            }
+        })
+    }
+
+    /// Checks an update of `refname` to commit `oid`.
+    ///
+    /// `signatures` must already be verified against the commit.
+    pub fn check(&self, refname: &str, oid: Oid, signatures: &[Signature]) -> Result<()> {
+        if !self.is_protected(refname) {
+            return Ok(());
+        }
+        if self.trusted.is_empty() {
+            return Err(Error::NoTrustedKeys {
+                refname: refname.to_string(),
+            });
+        }
+
+        let accepted = signatures
+            .iter()
+            .any(|signature| self.trusted.contains(&signature.key));
+
+        match accepted {
+            true => Ok(()),
+            false => Err(Error::UnsignedCommit {
+                refname: refname.to_string(),
+                oid,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use braid_hash::{HashAlgorithm, HashScheme};
+
+    use super::{sign, verify, Signature, SignaturePolicy, SigningKey};
+    use crate::{commit::CommitData, save::SaveData, Error};
+
+    #[test]
+    fn test_sign() {
+        let scheme = HashScheme::default();
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let (oid, signature) = sign(&CommitData::ROOT, scheme, &key).unwrap();
+
+        assert_eq!(verify(&CommitData::ROOT, scheme, &signature).unwrap(), oid);
+
+        // the signature covers the encoding, only the oid depends on the scheme
+        let plain = HashScheme::new(HashAlgorithm::Blake3);
+        let (plain_oid, plain_signature) = sign(&CommitData::ROOT, plain, &key).unwrap();
+        assert_ne!(plain_oid, oid);
+        assert_eq!(plain_signature, signature);
+        assert_eq!(
+            verify(&CommitData::ROOT, plain, &signature).unwrap(),
+            plain_oid
+        );
+
+        let bytes = Signature::from_bytes(signature.key().as_bytes(), &signature.to_bytes());
+        assert_eq!(bytes.unwrap(), signature);
+
+        let save = SaveData::new("a", CommitData::ROOT.date(), oid, oid);
+        assert!(matches!(
+            verify(&save, scheme, &signature),
+            Err(Error::InvalidSignature)
+        ));
+    }
+
+    #[test]
+    fn test_policy() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let other = SigningKey::from_bytes(&[8; 32]);
+        let scheme = HashScheme::default();
+        let (oid, signature) = sign(&CommitData::ROOT, scheme, &key).unwrap();
+        let (_, other_signature) = sign(&CommitData::ROOT, scheme, &other).unwrap();
+
+        let policy = SignaturePolicy::new()
+            .protect("refs/heads/main")
+            .protect("refs/tags/");
+        assert!(policy.is_protected("refs/heads/main"));
+        assert!(policy.is_protected("refs/heads/main/fix"));
+        assert!(policy.is_protected("refs/tags/v1"));
+        assert!(!policy.is_protected("refs/heads/main2"));
+        assert!(!policy.is_protected("refs/tagsv1"));
+
+        // without trusted keys, protected refs can't be updated at all
+        assert!(policy.check("refs/heads/dev", oid, &[]).is_ok());
+        assert!(matches!(
+            policy.check("refs/heads/main", oid, std::slice::from_ref(&signature)),
+            Err(Error::NoTrustedKeys { .. })
+        ));
+
+        let policy = policy.trust(key.verifying_key());
+        assert!(matches!(
+            policy.check("refs/heads/main", oid, &[]),
+            Err(Error::UnsignedCommit { .. })
+        ));
+        assert!(policy.check("refs/heads/main", oid, &[signature]).is_ok());
+        assert!(policy
+            .check("refs/heads/main", oid, &[other_signature])
+            .is_err());
+    }
+}
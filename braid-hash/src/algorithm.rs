@@ -0,0 +1,251 @@
+use sha2::{digest::typenum::Unsigned, Digest};
+
+use crate::{Oid, OID_LEN};
+
+// every algorithm must produce exactly one `Oid`
+const _: () = assert!(blake3::OUT_LEN == OID_LEN);
+const _: () = assert!(<sha2::Sha256 as sha2::digest::OutputSizeUser>::OutputSize::USIZE == OID_LEN);
+
+/// The hash function a repository derives its oids with.
+///
+/// A repository uses one algorithm for its whole lifetime, oids of different
+/// algorithms must never be mixed.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum HashAlgorithm {
+    #[default]
+    Blake3,
+    Sha256,
+}
+
+impl HashAlgorithm {
+    pub const ALL: [Self; 2] = [Self::Blake3, Self::Sha256];
+
+    /// The name recorded in repository metadata.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Blake3 => "blake3",
+            Self::Sha256 => "sha256",
+        }
+    }
+
+    pub fn hash(self, data: &[u8]) -> Oid {
+        match self {
+            Self::Blake3 => crate::hash(data),
+            Self::Sha256 => Oid(sha2::Sha256::digest(data).into()),
+        }
+    }
+}
+
+/// A context that keeps the hashes of one kind of data apart from the hashes
+/// of every other kind, so equal bytes of different kinds get different oids.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Domain(&'static str);
+
+impl Domain {
+    /// `context` must be a hardcoded string unique to the application and the
+    /// kind of data, as described for [`blake3::derive_key`].
+    pub const fn new(context: &'static str) -> Self {
+        Self(context)
+    }
+
+    pub const fn context(self) -> &'static str {
+        self.0
+    }
+}
+
+/// How a repository derives oids: the algorithm and whether data is hashed in
+/// its [`Domain`].
+///
+/// Without domains the hash only covers the data, which is how repositories
+/// were created before domains existed. With domains, BLAKE3 hashes in
+/// `derive_key` mode with the domain as context and SHA-256 hashes the
+/// length-prefixed context before the data.
+///
+/// The default, which new repositories use, is BLAKE3 with domains.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct HashScheme {
+    algorithm: HashAlgorithm,
+    domains: bool,
+}
+
+impl HashScheme {
+    pub const fn new(algorithm: HashAlgorithm) -> Self {
+        Self {
+            algorithm,
+            domains: false,
+        }
+    }
+
+    pub const fn with_domains(self) -> Self {
+        Self {
+            domains: true,
+            ..self
+        }
+    }
+
+    pub const fn algorithm(self) -> HashAlgorithm {
+        self.algorithm
+    }
+
+    pub const fn domains(self) -> bool {
+        self.domains
+    }
+
+    pub fn hash(self, domain: Domain, data: &[u8]) -> Oid {
+        match self.domains {
+            true => {
+                let mut hasher = self.hasher(domain);
+                hasher.update(data);
+                hasher.finalize()
+            }
+            false => self.algorithm.hash(data),
+        }
+    }
+
+    pub fn hasher(self, domain: Domain) -> crate::Hasher {
+        match self.domains {
+            true => crate::Hasher(Inner::with_domain(self.algorithm, domain)),
+            false => crate::Hasher::with_algorithm(self.algorithm),
+        }
+    }
+}
+
+impl Default for HashScheme {
+    fn default() -> Self {
+        Self::new(HashAlgorithm::Blake3).with_domains()
+    }
+}
+
+impl std::fmt::Display for HashScheme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.domains {
+            true => write!(f, "{} with domains", self.algorithm),
+            false => write!(f, "{}", self.algorithm),
+        }
+    }
+}
+
+impl std::fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for HashAlgorithm {
+    type Err = UnknownHashAlgorithmError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|algorithm| algorithm.as_str() == s)
+            .ok_or_else(|| UnknownHashAlgorithmError(s.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownHashAlgorithmError(pub String);
+
+impl std::fmt::Display for UnknownHashAlgorithmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Unknown hash algorithm: {:?}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownHashAlgorithmError {}
+
+pub(crate) enum Inner {
+    Blake3(Box<blake3::Hasher>),
+    Sha256(sha2::Sha256),
+}
+
+impl Inner {
+    pub(crate) fn new(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Blake3 => Self::Blake3(Box::new(blake3::Hasher::new())),
+            HashAlgorithm::Sha256 => Self::Sha256(sha2::Sha256::new()),
+        }
+    }
+
+    fn with_domain(algorithm: HashAlgorithm, domain: Domain) -> Self {
+        match algorithm {
+            HashAlgorithm::Blake3 => {
+                Self::Blake3(Box::new(blake3::Hasher::new_derive_key(domain.context())))
+            }
+            HashAlgorithm::Sha256 => {
+                let context = domain.context().as_bytes();
+                let mut hasher = sha2::Sha256::new();
+                hasher.update((context.len() as u64).to_le_bytes());
+                hasher.update(context);
+                Self::Sha256(hasher)
+            }
+        }
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Blake3(hasher) => {
+                hasher.update(data);
+            }
+            Self::Sha256(hasher) => hasher.update(data),
+        }
+    }
+
+    pub(crate) fn finalize(self) -> [u8; OID_LEN] {
+        match self {
+            Self::Blake3(hasher) => hasher.finalize().into(),
+            Self::Sha256(hasher) => hasher.finalize().into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Domain, HashAlgorithm, HashScheme};
+    use crate::{Hasher, Oid};
+
+    #[test]
+    fn test_algorithms() {
+        let sha256 = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        assert_eq!(
+            HashAlgorithm::Sha256.hash(b"abc"),
+            Oid::try_from_str(sha256).unwrap()
+        );
+        assert_eq!(HashAlgorithm::Blake3.hash(b"abc"), crate::hash(b"abc"));
+
+        for algorithm in HashAlgorithm::ALL {
+            let mut hasher = Hasher::with_algorithm(algorithm);
+            hasher.update(b"a");
+            hasher.update(b"bc");
+            assert_eq!(hasher.finalize(), algorithm.hash(b"abc"));
+            assert_eq!(algorithm.to_string().parse(), Ok(algorithm));
+        }
+
+        assert!("md5".parse::<HashAlgorithm>().is_err());
+    }
+
+    #[test]
+    fn test_domains() {
+        let a = Domain::new("braid-hash test a");
+        let b = Domain::new("braid-hash test b");
+
+        for algorithm in HashAlgorithm::ALL {
+            let plain = HashScheme::new(algorithm);
+            assert_eq!(plain.hash(a, b"abc"), algorithm.hash(b"abc"));
+            assert_eq!(plain.hash(a, b"abc"), plain.hash(b, b"abc"));
+
+            let scheme = plain.with_domains();
+            assert_ne!(scheme.hash(a, b"abc"), algorithm.hash(b"abc"));
+            assert_ne!(scheme.hash(a, b"abc"), scheme.hash(b, b"abc"));
+
+            let mut hasher = scheme.hasher(a);
+            hasher.update(b"ab");
+            hasher.update(b"c");
+            assert_eq!(hasher.finalize(), scheme.hash(a, b"abc"));
+        }
+
+        let key = blake3::derive_key("braid-hash test a", b"abc");
+        let scheme = HashScheme::new(HashAlgorithm::Blake3).with_domains();
+        assert_eq!(scheme.hash(a, b"abc").into_inner(), key);
+        assert_eq!(HashScheme::default(), scheme);
+    }
+}
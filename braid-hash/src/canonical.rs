@@ -0,0 +1,707 @@
+//! A canonical MessagePack encoding of serde values.
+//!
+//! [`hash_obj`](crate::hash_obj) hashes whatever rmp-serde writes, which
+//! depends on struct field order and integer types. The canonical encoding
+//! only depends on the value, and is frozen: a value keeps its encoding, and
+//! so its oid, across versions of this crate and across platforms.
+//!
+//! Values are encoded as:
+//!
+//! - integers of any type in the shortest MessagePack integer form, so `1u8`
+//!   and `1i64` are equal; 128 bit integers must fit 64 bits
+//! - floats of either type as float 64, so `1.5f32` and `1.5f64` are equal
+//!   and no float equals an integer; NaN is rejected and `-0.0` is not `0.0`
+//! - strings and chars as str, byte buffers (`serialize_bytes`) as bin
+//! - `None`, `()` and unit structs as nil, newtype structs as their contents
+//! - `Some(x)` as an array of `x` alone, so `Some(None)` is not `None`
+//! - sequences, tuples and tuple structs as arrays
+//! - maps and structs as maps sorted by the encoding of their keys; keys must
+//!   be unique, struct fields are keyed by name
+//! - enum variants like serde's externally tagged JSON: a unit variant as its
+//!   name, any other variant as a map from its name to its contents
+//!
+//! So renaming a field or variant changes the oid, while reordering fields or
+//! switching between `HashMap` and `BTreeMap` does not.
+//!
+//! Values of different types can share an encoding, such as `Some(1)` and
+//! `vec![1]`, values of one type never do.
+
+use serde::{ser, Serialize};
+
+use crate::{Domain, HashScheme, Oid};
+
+/// The domain canonical encodings are hashed in by schemes with domains.
+pub const DOMAIN: Domain = Domain::new("braid-hash 2026-10-18 canonical");
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CanonicalError {
+    /// NaN has no canonical encoding.
+    NanFloat,
+    IntegerOutOfRange,
+    /// A map or struct has the same key more than once.
+    DuplicateKey,
+    /// More than `u32::MAX` items or bytes.
+    TooLong,
+    Custom(String),
+}
+
+impl std::fmt::Display for CanonicalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NanFloat => f.write_str("NaN has no canonical encoding"),
+            Self::IntegerOutOfRange => f.write_str("Integer does not fit 64 bits"),
+            Self::DuplicateKey => f.write_str("Duplicate map key"),
+            Self::TooLong => f.write_str("Value has more than u32::MAX items"),
+            Self::Custom(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl std::error::Error for CanonicalError {}
+
+impl ser::Error for CanonicalError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Self::Custom(msg.to_string())
+    }
+}
+
+type Result<T> = std::result::Result<T, CanonicalError>;
+
+/// Encodes `data` canonically, see the [module docs](self).
+pub fn to_canonical_vec<T: Serialize + ?Sized>(data: &T) -> Result<Vec<u8>> {
+    data.serialize(Encoder)
+}
+
+/// Hashes the canonical encoding of `data` with the default scheme.
+pub fn hash_canonical<T: Serialize + ?Sized>(data: &T) -> Result<Oid> {
+    hash_canonical_with(HashScheme::default(), data)
+}
+
+/// Hashes the canonical encoding of `data` with `scheme`, in [`DOMAIN`].
+pub fn hash_canonical_with<T: Serialize + ?Sized>(scheme: HashScheme, data: &T) -> Result<Oid> {
+    Ok(scheme.hash(DOMAIN, &to_canonical_vec(data)?))
+}
+
+fn uint(value: u64) -> Vec<u8> {
+    match value {
+        0..=0x7f => vec![value as u8],
+        0x80..=0xff => vec![0xcc, value as u8],
+        0x100..=0xffff => [&[0xcd][..], &(value as u16).to_be_bytes()].concat(),
+        0x1_0000..=0xffff_ffff => [&[0xce][..], &(value as u32).to_be_bytes()].concat(),
+        _ => [&[0xcf][..], &value.to_be_bytes()].concat(),
+    }
+}
+
+fn int(value: i64) -> Vec<u8> {
+    match value {
+        0.. => uint(value as u64),
+        -32..=-1 => vec![value as u8],
+        -0x80..=-33 => vec![0xd0, value as u8],
+        -0x8000..=-0x81 => [&[0xd1][..], &(value as i16).to_be_bytes()].concat(),
+        -0x8000_0000..=-0x8001 => [&[0xd2][..], &(value as i32).to_be_bytes()].concat(),
+        _ => [&[0xd3][..], &value.to_be_bytes()].concat(),
+    }
+}
+
+/// Markers of a length prefix: the fix marker with its limit, then the 8, 16
+/// and 32 bit markers.
+struct LenMarkers {
+    fix: Option<(u8, usize)>,
+    sized: [Option<u8>; 3],
+}
+
+const STR: LenMarkers = LenMarkers {
+    fix: Some((0xa0, 32)),
+    sized: [Some(0xd9), Some(0xda), Some(0xdb)],
+};
+const BIN: LenMarkers = LenMarkers {
+    fix: None,
+    sized: [Some(0xc4), Some(0xc5), Some(0xc6)],
+};
+const ARRAY: LenMarkers = LenMarkers {
+    fix: Some((0x90, 16)),
+    sized: [None, Some(0xdc), Some(0xdd)],
+};
+const MAP: LenMarkers = LenMarkers {
+    fix: Some((0x80, 16)),
+    sized: [None, Some(0xde), Some(0xdf)],
+};
+
+fn write_len(buf: &mut Vec<u8>, markers: &LenMarkers, len: usize) -> Result<()> {
+    if let Some((marker, limit)) = markers.fix {
+        if len < limit {
+            buf.push(marker | len as u8);
+            return Ok(());
+        }
+    }
+    match markers.sized {
+        [Some(marker), ..] if len <= u8::MAX as usize => buf.extend([marker, len as u8]),
+        [_, Some(marker), _] if len <= u16::MAX as usize => {
+            buf.push(marker);
+            buf.extend((len as u16).to_be_bytes());
+        }
+        [.., Some(marker)] if len <= u32::MAX as usize => {
+            buf.push(marker);
+            buf.extend((len as u32).to_be_bytes());
+        }
+        _ => return Err(CanonicalError::TooLong),
+    }
+    Ok(())
+}
+
+fn with_len(markers: &LenMarkers, len: usize, data: &[u8]) -> Result<Vec<u8>> {
+    let mut buf = Vec::with_capacity(data.len() + 5);
+    write_len(&mut buf, markers, len)?;
+    buf.extend_from_slice(data);
+    Ok(buf)
+}
+
+/// Serializes one value into its encoding.
+struct Encoder;
+
+impl ser::Serializer for Encoder {
+    type Ok = Vec<u8>;
+    type Error = CanonicalError;
+
+    type SerializeSeq = Array;
+    type SerializeTuple = Array;
+    type SerializeTupleStruct = Array;
+    type SerializeTupleVariant = Variant<Array>;
+    type SerializeMap = Map;
+    type SerializeStruct = Map;
+    type SerializeStructVariant = Variant<Map>;
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    fn serialize_bool(self, v: bool) -> Result<Vec<u8>> {
+        Ok(vec![if v { 0xc3 } else { 0xc2 }])
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Vec<u8>> {
+        Ok(int(v.into()))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Vec<u8>> {
+        Ok(int(v.into()))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Vec<u8>> {
+        Ok(int(v.into()))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Vec<u8>> {
+        Ok(int(v))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Vec<u8>> {
+        match (i64::try_from(v), u64::try_from(v)) {
+            (Ok(v), _) => Ok(int(v)),
+            (_, Ok(v)) => Ok(uint(v)),
+            _ => Err(CanonicalError::IntegerOutOfRange),
+        }
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Vec<u8>> {
+        Ok(uint(v.into()))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Vec<u8>> {
+        Ok(uint(v.into()))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Vec<u8>> {
+        Ok(uint(v.into()))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Vec<u8>> {
+        Ok(uint(v))
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Vec<u8>> {
+        let v = u64::try_from(v).map_err(|_| CanonicalError::IntegerOutOfRange)?;
+        Ok(uint(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Vec<u8>> {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Vec<u8>> {
+        if v.is_nan() {
+            return Err(CanonicalError::NanFloat);
+        }
+        Ok([&[0xcb][..], &v.to_be_bytes()].concat())
+    }
+
+    fn serialize_char(self, v: char) -> Result<Vec<u8>> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Vec<u8>> {
+        with_len(&STR, v.len(), v.as_bytes())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Vec<u8>> {
+        with_len(&BIN, v.len(), v)
+    }
+
+    fn serialize_none(self) -> Result<Vec<u8>> {
+        Ok(vec![0xc0])
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Vec<u8>> {
+        let mut array = Array::new(Some(1));
+        array.push(value)?;
+        array.finish()
+    }
+
+    fn serialize_unit(self) -> Result<Vec<u8>> {
+        self.serialize_none()
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Vec<u8>> {
+        self.serialize_none()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Vec<u8>> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Vec<u8>> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Vec<u8>> {
+        variant_map(variant, value.serialize(self)?)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Array> {
+        Ok(Array::new(len))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Array> {
+        Ok(Array::new(Some(len)))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<Array> {
+        Ok(Array::new(Some(len)))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Variant<Array>> {
+        Ok(Variant {
+            name: variant,
+            inner: Array::new(Some(len)),
+        })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Map> {
+        Ok(Map::new(len))
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Map> {
+        Ok(Map::new(Some(len)))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Variant<Map>> {
+        Ok(Variant {
+            name: variant,
+            inner: Map::new(Some(len)),
+        })
+    }
+}
+
+struct Array {
+    len: usize,
+    items: Vec<u8>,
+}
+
+impl Array {
+    fn new(len: Option<usize>) -> Self {
+        Self {
+            len: 0,
+            items: Vec::with_capacity(len.unwrap_or(0)),
+        }
+    }
+
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.items.extend(value.serialize(Encoder)?);
+        self.len += 1;
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Vec<u8>> {
+        with_len(&ARRAY, self.len, &self.items)
+    }
+}
+
+impl ser::SerializeSeq for Array {
+    type Ok = Vec<u8>;
+    type Error = CanonicalError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Vec<u8>> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for Array {
+    type Ok = Vec<u8>;
+    type Error = CanonicalError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Vec<u8>> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for Array {
+    type Ok = Vec<u8>;
+    type Error = CanonicalError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Vec<u8>> {
+        self.finish()
+    }
+}
+
+/// Map entries by encoded key and value, sorted when the map ends.
+struct Map {
+    entries: Vec<(Vec<u8>, Vec<u8>)>,
+    key: Option<Vec<u8>>,
+}
+
+impl Map {
+    fn new(len: Option<usize>) -> Self {
+        Self {
+            entries: Vec::with_capacity(len.unwrap_or(0)),
+            key: None,
+        }
+    }
+
+    fn finish(mut self) -> Result<Vec<u8>> {
+        self.entries.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        if self.entries.windows(2).any(|pair| pair[0].0 == pair[1].0) {
+            return Err(CanonicalError::DuplicateKey);
+        }
+
+        let mut buf = Vec::new();
+        write_len(&mut buf, &MAP, self.entries.len())?;
+        for (key, value) in self.entries {
+            buf.extend(key);
+            buf.extend(value);
+        }
+        Ok(buf)
+    }
+}
+
+impl ser::SerializeMap for Map {
+    type Ok = Vec<u8>;
+    type Error = CanonicalError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        self.key = Some(key.serialize(Encoder)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let key = self.key.take().ok_or_else(|| {
+            CanonicalError::Custom("serialize_value called before serialize_key".to_string())
+        })?;
+        self.entries.push((key, value.serialize(Encoder)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Vec<u8>> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for Map {
+    type Ok = Vec<u8>;
+    type Error = CanonicalError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        let key = ser::Serializer::serialize_str(Encoder, key)?;
+        self.entries.push((key, value.serialize(Encoder)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Vec<u8>> {
+        self.finish()
+    }
+}
+
+/// A tuple or struct variant, encoded as a map from its name to `inner`.
+struct Variant<T> {
+    name: &'static str,
+    inner: T,
+}
+
+/// Encodes a map from `name` to the encoded `value`.
+fn variant_map(name: &'static str, value: Vec<u8>) -> Result<Vec<u8>> {
+    let mut buf = vec![0x81];
+    buf.extend(ser::Serializer::serialize_str(Encoder, name)?);
+    buf.extend(value);
+    Ok(buf)
+}
+
+impl ser::SerializeTupleVariant for Variant<Array> {
+    type Ok = Vec<u8>;
+    type Error = CanonicalError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.inner.push(value)
+    }
+
+    fn end(self) -> Result<Vec<u8>> {
+        variant_map(self.name, self.inner.finish()?)
+    }
+}
+
+impl ser::SerializeStructVariant for Variant<Map> {
+    type Ok = Vec<u8>;
+    type Error = CanonicalError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        ser::SerializeStruct::serialize_field(&mut self.inner, key, value)
+    }
+
+    fn end(self) -> Result<Vec<u8>> {
+        variant_map(self.name, self.inner.finish()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+
+    use serde::Serialize;
+
+    use super::{hash_canonical, hash_canonical_with, to_canonical_vec, CanonicalError};
+    use crate::{HashAlgorithm, HashScheme, Oid};
+
+    #[test]
+    fn test_integers() {
+        assert_eq!(to_canonical_vec(&1u8), to_canonical_vec(&1i64));
+        assert_eq!(to_canonical_vec(&200u64).unwrap(), [0xcc, 200]);
+        assert_eq!(to_canonical_vec(&-1i32).unwrap(), [0xff]);
+        assert_eq!(to_canonical_vec(&-200i64).unwrap(), [0xd1, 0xff, 0x38]);
+        assert_eq!(
+            to_canonical_vec(&(u64::MAX as i128)),
+            to_canonical_vec(&u64::MAX)
+        );
+        assert_eq!(
+            to_canonical_vec(&i128::MIN),
+            Err(CanonicalError::IntegerOutOfRange)
+        );
+    }
+
+    #[test]
+    fn test_floats() {
+        assert_eq!(to_canonical_vec(&1.5f32), to_canonical_vec(&1.5f64));
+        assert_ne!(to_canonical_vec(&1.0f64), to_canonical_vec(&1u8));
+        assert_ne!(to_canonical_vec(&-0.0f64), to_canonical_vec(&0.0f64));
+        assert_eq!(to_canonical_vec(&f64::NAN), Err(CanonicalError::NanFloat));
+    }
+
+    #[test]
+    fn test_maps() {
+        #[derive(Serialize)]
+        struct A {
+            name: &'static str,
+            size: u32,
+        }
+
+        #[derive(Serialize)]
+        struct B {
+            size: u64,
+            name: String,
+        }
+
+        let a = A { name: "x", size: 3 };
+        let b = B {
+            size: 3,
+            name: "x".to_string(),
+        };
+        assert_eq!(hash_canonical(&a), hash_canonical(&b));
+
+        let hashed: HashMap<_, _> = (0..100u32).map(|i| (i.to_string(), i)).collect();
+        let sorted: BTreeMap<_, _> = hashed.clone().into_iter().collect();
+        assert_eq!(hash_canonical(&hashed), hash_canonical(&sorted));
+
+        let duplicate = [("a", 1), ("a", 2)];
+        assert_eq!(
+            to_canonical_vec(&Pairs(&duplicate)),
+            Err(CanonicalError::DuplicateKey)
+        );
+    }
+
+    #[test]
+    fn test_enums() {
+        #[derive(Serialize)]
+        enum E {
+            Unit,
+            Newtype(u8),
+            Struct { a: u8 },
+        }
+
+        assert_eq!(to_canonical_vec(&E::Unit), to_canonical_vec("Unit"));
+        assert_eq!(
+            to_canonical_vec(&E::Newtype(1)).unwrap(),
+            [0x81, 0xa7, b'N', b'e', b'w', b't', b'y', b'p', b'e', 1]
+        );
+        let mut inner = BTreeMap::new();
+        inner.insert("a", 2u8);
+        let mut outer = BTreeMap::new();
+        outer.insert("Struct", inner);
+        assert_eq!(
+            to_canonical_vec(&E::Struct { a: 2 }),
+            to_canonical_vec(&outer)
+        );
+    }
+
+    #[test]
+    fn test_options() {
+        assert_eq!(to_canonical_vec(&None::<u8>).unwrap(), [0xc0]);
+        assert_eq!(to_canonical_vec(&Some(1u8)).unwrap(), [0x91, 1]);
+        assert_ne!(
+            to_canonical_vec(&Some(None::<u8>)),
+            to_canonical_vec(&None::<Option<u8>>)
+        );
+    }
+
+    #[test]
+    fn test_value_without_key() {
+        struct ValueOnly;
+
+        impl Serialize for ValueOnly {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                use serde::ser::SerializeMap;
+
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_value(&1u8)?;
+                map.end()
+            }
+        }
+
+        assert!(matches!(
+            to_canonical_vec(&ValueOnly),
+            Err(CanonicalError::Custom(_))
+        ));
+    }
+
+    /// Pins the encoding, which must never change.
+    #[test]
+    fn test_vectors() {
+        #[derive(Serialize)]
+        enum Kind {
+            File { size: u64 },
+        }
+
+        #[derive(Serialize)]
+        struct Entry {
+            name: &'static str,
+            tags: Vec<i32>,
+            parent: Option<u32>,
+            ratio: f32,
+            kind: Kind,
+            bytes: Bin,
+        }
+
+        struct Bin(&'static [u8]);
+
+        impl Serialize for Bin {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_bytes(self.0)
+            }
+        }
+
+        let entry = Entry {
+            name: "a",
+            tags: vec![-1, 300],
+            parent: Some(7),
+            ratio: 0.5,
+            kind: Kind::File { size: 1 },
+            bytes: Bin(b"\x00\xff"),
+        };
+        let encoded = concat!(
+            "86a46b696e6481a446696c6581a473697a6501a46e616d65a161a474616773",
+            "92ffcd012ca56279746573c40200ffa5726174696fcb3fe0000000000000",
+            "a6706172656e749107",
+        );
+        assert_eq!(to_canonical_vec(&entry).unwrap(), hex(encoded));
+
+        let oid = "b38cfe12244cc7de2049c43487f0b6da49b9f074cb1713b1df4fa4247079bc61";
+        assert_eq!(
+            hash_canonical(&entry).unwrap(),
+            Oid::try_from_str(oid).unwrap()
+        );
+        let sha256 = "880527c04432f4dd2f36c79428756590bdbdf1c9c9597603a3fc362d44c9104a";
+        assert_eq!(
+            hash_canonical_with(HashScheme::new(HashAlgorithm::Sha256), &entry).unwrap(),
+            Oid::try_from_str(sha256).unwrap()
+        );
+    }
+
+    fn hex(hex: &str) -> Vec<u8> {
+        hex.as_bytes()
+            .chunks(2)
+            .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).unwrap(), 16).unwrap())
+            .collect()
+    }
+
+    /// Serializes as a map, keeping duplicate keys.
+    struct Pairs<'a>(&'a [(&'a str, u8)]);
+
+    impl Serialize for Pairs<'_> {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_map(self.0.iter().map(|(k, v)| (k, v)))
+        }
+    }
+}
@@ -1,9 +1,21 @@
 use std::hint::unreachable_unchecked;
 
+mod algorithm;
+pub mod canonical;
+#[cfg(feature = "parallel")]
+mod parallel;
 #[cfg(feature = "postgres")]
 mod postgres;
+#[cfg(feature = "serde")]
+mod serde_hex;
+pub mod stream;
 
-pub const OID_LEN: usize = blake3::OUT_LEN;
+pub use algorithm::{Domain, HashAlgorithm, HashScheme, UnknownHashAlgorithmError};
+#[cfg(feature = "parallel")]
+pub use parallel::{hash_batch, hash_file, hash_reader_parallel};
+
+/// The length of an oid, the same for every [`HashAlgorithm`].
+pub const OID_LEN: usize = 32;
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash)]
 pub struct Oid([u8; OID_LEN]);
@@ -38,8 +50,32 @@ impl std::fmt::Debug for HexByte {
     }
 }
 
-#[derive(Debug)]
-pub struct InvalidOidStringError<S>(S);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidOidStringError {
+    /// The string is not exactly `OID_LEN * 2` bytes long.
+    InvalidLength(usize),
+
+    /// The string contains a character that is not a hex digit.
+    InvalidCharacter { index: usize, character: char },
+}
+
+impl std::fmt::Display for InvalidOidStringError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidLength(len) => write!(
+                f,
+                "Invalid oid length: expected {} hex characters, got {len}",
+                OID_LEN * 2
+            ),
+            Self::InvalidCharacter { index, character } => write!(
+                f,
+                "Invalid character in oid at index {index}: {character:?}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for InvalidOidStringError {}
 
 impl Oid {
     pub const ZERO: Self = Self([0; OID_LEN]);
@@ -53,31 +89,37 @@ impl Oid {
         Self(bytes)
     }
 
-    pub fn try_from_str<S: AsRef<str>>(hex: S) -> Result<Self, InvalidOidStringError<S>> {
-        let hex_bytes = hex.as_ref().as_bytes();
+    /// Parses a hex string of exactly `OID_LEN * 2` characters.
+    /// Both lowercase and uppercase hex digits are accepted.
+    pub fn try_from_str<S: AsRef<str>>(hex: S) -> Result<Self, InvalidOidStringError> {
+        let hex = hex.as_ref();
+        let hex_bytes = hex.as_bytes();
 
         if hex_bytes.len() != OID_LEN * 2 {
-            return Err(InvalidOidStringError(hex));
+            return Err(InvalidOidStringError::InvalidLength(hex_bytes.len()));
         }
 
-        let mut bytes = [0; OID_LEN];
-
-        for i in 0..OID_LEN {
-            let hi = hex_bytes[i * 2];
-            let lo = hex_bytes[i * 2 + 1];
-
-            fn to_byte(c: u8) -> u8 {
-                match c {
-                    b'0'..=b'9' => c - b'0',
-                    b'a'..=b'f' => c - b'a' + 10,
-                    _ => 0,
-                }
+        let to_nibble = |index: usize| -> Result<u8, InvalidOidStringError> {
+            let c = hex_bytes[index];
+            match c {
+                b'0'..=b'9' => Ok(c - b'0'),
+                b'a'..=b'f' => Ok(c - b'a' + 10),
+                b'A'..=b'F' => Ok(c - b'A' + 10),
+                // every byte before `index` is ascii, so `index` is on a char boundary
+                _ => Err(InvalidOidStringError::InvalidCharacter {
+                    index,
+                    character: hex[index..].chars().next().unwrap_or_default(),
+                }),
             }
+        };
+
+        let mut bytes = [0; OID_LEN];
 
-            let hi = to_byte(hi);
-            let lo = to_byte(lo);
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            let hi = to_nibble(i * 2)?;
+            let lo = to_nibble(i * 2 + 1)?;
 
-            bytes[i] = (hi << 4) | lo;
+            *byte = (hi << 4) | lo;
         }
 
         Ok(Self(bytes))
@@ -138,33 +180,64 @@ impl std::fmt::Debug for Oid {
     }
 }
 
-type Serializer = rmp_serde::Serializer<blake3::Hasher>;
+impl std::str::FromStr for Oid {
+    type Err = InvalidOidStringError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::try_from_str(s)
+    }
+}
+
+impl TryFrom<&str> for Oid {
+    type Error = InvalidOidStringError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Self::try_from_str(value)
+    }
+}
+
+type Serializer = rmp_serde::Serializer<Hasher>;
+
+type SerializeError = rmp_serde::encode::Error;
 
 pub trait ContentHash {
-    fn hash(&self) -> Result<Oid, <&mut Serializer as serde::ser::Serializer>::Error>;
+    fn hash(&self) -> Result<Oid, SerializeError>;
+
+    /// Hashes the [canonical] encoding.
+    fn canonical_hash(&self) -> Result<Oid, canonical::CanonicalError>;
 }
 
 impl<T: serde::Serialize> ContentHash for T {
-    fn hash(&self) -> Result<Oid, <&mut Serializer as serde::ser::Serializer>::Error> {
+    fn hash(&self) -> Result<Oid, SerializeError> {
         hash_obj(self)
     }
+
+    fn canonical_hash(&self) -> Result<Oid, canonical::CanonicalError> {
+        canonical::hash_canonical(self)
+    }
 }
 
-pub fn hash_obj<T: serde::Serialize>(
+/// Hashes `data` as rmp-serde encodes it, which depends on field order and
+/// integer types. [`canonical::hash_canonical`] only depends on the value.
+pub fn hash_obj<T: serde::Serialize>(data: &T) -> Result<Oid, SerializeError> {
+    hash_obj_with(HashAlgorithm::default(), data)
+}
+
+pub fn hash_obj_with<T: serde::Serialize>(
+    algorithm: HashAlgorithm,
     data: &T,
-) -> Result<Oid, <&mut Serializer as serde::ser::Serializer>::Error> {
-    let hasher = blake3::Hasher::new();
-    let mut ser = Serializer::new(hasher);
+) -> Result<Oid, SerializeError> {
+    let mut ser = Serializer::new(Hasher::with_algorithm(algorithm));
     data.serialize(&mut ser)?;
-    let hash = ser.into_inner().finalize();
-    Ok(Oid(hash.into()))
+    Ok(ser.into_inner().finalize())
 }
 
+/// Hashes `data` with the default algorithm, BLAKE3.
 pub fn hash(data: &[u8]) -> Oid {
     Oid(blake3::hash(data).into())
 }
 
-pub struct Hasher(blake3::Hasher);
+pub struct Hasher(algorithm::Inner);
 
 impl Default for Hasher {
     fn default() -> Self {
@@ -174,7 +247,11 @@ impl Default for Hasher {
 
 impl Hasher {
     pub fn new() -> Self {
-        Self(blake3::Hasher::new())
+        Self::with_algorithm(HashAlgorithm::default())
+    }
+
+    pub fn with_algorithm(algorithm: HashAlgorithm) -> Self {
+        Self(algorithm::Inner::new(algorithm))
     }
 
     pub fn update(&mut self, data: &[u8]) {
@@ -182,24 +259,21 @@ impl Hasher {
     }
 
     pub fn finalize(self) -> Oid {
-        Oid(self.0.finalize().into())
+        Oid(self.0.finalize())
     }
 }
 
 impl std::io::Write for Hasher {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        self.0.write(buf)
+        self.update(buf);
+        Ok(buf.len())
     }
 
     fn flush(&mut self) -> std::io::Result<()> {
-        self.0.flush()
+        Ok(())
     }
 }
 
-mod sealed {
-    pub trait Sealed {}
-}
-
 #[cfg(test)]
 mod tests {
     #[test]
@@ -214,4 +288,41 @@ mod tests {
             assert_eq!(oid, oid2);
         }
     }
+
+    #[test]
+    fn test_uppercase() {
+        let oid = crate::Oid::from_bytes(std::array::from_fn(|i| 7 * i as u8));
+        let upper = oid.to_hex_string().to_uppercase();
+
+        assert_eq!(upper.parse::<crate::Oid>().unwrap(), oid);
+    }
+
+    #[test]
+    fn test_invalid_string() {
+        use crate::InvalidOidStringError;
+
+        let err = crate::Oid::try_from_str("abc").unwrap_err();
+        assert_eq!(err, InvalidOidStringError::InvalidLength(3));
+
+        let mut hex = crate::Oid::ZERO.to_hex_string();
+        hex.replace_range(10..11, "g");
+        let err = crate::Oid::try_from(hex.as_str()).unwrap_err();
+        assert_eq!(
+            err,
+            InvalidOidStringError::InvalidCharacter {
+                index: 10,
+                character: 'g'
+            }
+        );
+
+        let hex = format!("{}é", "0".repeat(crate::OID_LEN * 2 - 2));
+        let err = crate::Oid::try_from_str(hex).unwrap_err();
+        assert_eq!(
+            err,
+            InvalidOidStringError::InvalidCharacter {
+                index: crate::OID_LEN * 2 - 2,
+                character: 'é'
+            }
+        );
+    }
 }
@@ -0,0 +1,146 @@
+//! Hashing large inputs, and many small ones, on every core.
+
+use std::{
+    fs::File,
+    io::{self, Read},
+    path::Path,
+};
+
+use rayon::prelude::*;
+
+use crate::{algorithm::Inner, Domain, HashScheme, Hasher, Oid};
+
+/// Reads fill buffers this large, so BLAKE3 can split each between threads.
+const READ_BUF_LEN: usize = 4 << 20;
+
+impl Hasher {
+    /// Like [`update`](Self::update), but splits large inputs between threads.
+    /// Only BLAKE3 can, other algorithms hash sequentially.
+    pub fn update_parallel(&mut self, data: &[u8]) {
+        match &mut self.0 {
+            Inner::Blake3(hasher) => {
+                hasher.update_rayon(data);
+            }
+            Inner::Sha256(_) => self.update(data),
+        }
+    }
+
+    /// Hashes the contents of the file at `path`. Large files are memory
+    /// mapped and hashed with [`update_parallel`](Self::update_parallel).
+    pub fn update_file(&mut self, path: impl AsRef<Path>) -> io::Result<()> {
+        match &mut self.0 {
+            Inner::Blake3(hasher) => {
+                hasher.update_mmap_rayon(path)?;
+            }
+            Inner::Sha256(_) => self.update_reader_parallel(File::open(path)?)?,
+        }
+        Ok(())
+    }
+
+    /// Hashes everything `reader` returns, with
+    /// [`update_parallel`](Self::update_parallel).
+    pub fn update_reader_parallel(&mut self, mut reader: impl Read) -> io::Result<()> {
+        let mut buf = vec![0; READ_BUF_LEN];
+        loop {
+            let len = read_full(&mut reader, &mut buf)?;
+            if len == 0 {
+                return Ok(());
+            }
+            self.update_parallel(&buf[..len]);
+        }
+    }
+}
+
+impl HashScheme {
+    /// Hashes each of `items` in `domain`, spreading them between threads.
+    pub fn hash_batch<T: AsRef<[u8]> + Sync>(self, domain: Domain, items: &[T]) -> Vec<Oid> {
+        items
+            .par_iter()
+            .map(|item| self.hash(domain, item.as_ref()))
+            .collect()
+    }
+}
+
+/// Hashes the file at `path` with the default algorithm, see
+/// [`Hasher::update_file`].
+pub fn hash_file(path: impl AsRef<Path>) -> io::Result<Oid> {
+    let mut hasher = Hasher::new();
+    hasher.update_file(path)?;
+    Ok(hasher.finalize())
+}
+
+/// Hashes everything `reader` returns with the default algorithm, see
+/// [`Hasher::update_reader_parallel`].
+pub fn hash_reader_parallel(reader: impl Read) -> io::Result<Oid> {
+    let mut hasher = Hasher::new();
+    hasher.update_reader_parallel(reader)?;
+    Ok(hasher.finalize())
+}
+
+/// Hashes each of `items` with the default algorithm, spreading them between
+/// threads.
+pub fn hash_batch<T: AsRef<[u8]> + Sync>(items: &[T]) -> Vec<Oid> {
+    items
+        .par_iter()
+        .map(|item| crate::hash(item.as_ref()))
+        .collect()
+}
+
+/// Reads until `buf` is full or `reader` is exhausted.
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match reader.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(read) => len += read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use crate::{Domain, HashAlgorithm, HashScheme, Hasher};
+
+    #[test]
+    fn test_parallel() {
+        let data: Vec<u8> = (0..super::READ_BUF_LEN + 5).map(|i| i as u8).collect();
+        let expected = crate::hash(&data);
+
+        assert_eq!(
+            super::hash_reader_parallel(data.as_slice()).unwrap(),
+            expected
+        );
+
+        let path = std::env::temp_dir().join(format!("braid-hash-{}", std::process::id()));
+        std::fs::File::create(&path)
+            .unwrap()
+            .write_all(&data)
+            .unwrap();
+        let oid = super::hash_file(&path);
+
+        let mut hasher = Hasher::with_algorithm(HashAlgorithm::Sha256);
+        hasher.update_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(oid.unwrap(), expected);
+        assert_eq!(hasher.finalize(), HashAlgorithm::Sha256.hash(&data));
+    }
+
+    #[test]
+    fn test_batch() {
+        let items = ["a", "bc", "", "def"];
+        let oids = super::hash_batch(&items);
+        assert_eq!(oids[1], crate::hash(b"bc"));
+
+        let domain = Domain::new("braid-hash test batch");
+        let scheme = HashScheme::new(HashAlgorithm::Sha256).with_domains();
+        let oids = scheme.hash_batch(domain, &items);
+        assert_eq!(oids.len(), items.len());
+        assert_eq!(oids[3], scheme.hash(domain, b"def"));
+    }
+}
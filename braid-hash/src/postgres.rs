@@ -1,4 +1,7 @@
-use sqlx::{Decode, Encode, Postgres, Type};
+use sqlx::{
+    postgres::{PgHasArrayType, PgTypeInfo},
+    Decode, Encode, Postgres, Type,
+};
 
 use crate::{Oid, OID_LEN};
 
@@ -25,3 +28,9 @@ impl Type<Postgres> for Oid {
         <[u8; OID_LEN] as Type<Postgres>>::type_info()
     }
 }
+
+impl PgHasArrayType for Oid {
+    fn array_type_info() -> PgTypeInfo {
+        <&[u8] as PgHasArrayType>::array_type_info()
+    }
+}
@@ -0,0 +1,48 @@
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::Oid;
+
+impl Serialize for Oid {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_hex_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Oid {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(OidVisitor)
+    }
+}
+
+struct OidVisitor;
+
+impl de::Visitor<'_> for OidVisitor {
+    type Value = Oid;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "a string of {} hex characters", crate::OID_LEN * 2)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        Oid::try_from_str(v).map_err(E::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{de::value::StrDeserializer, Deserialize};
+
+    use crate::Oid;
+
+    #[test]
+    fn test_deserialize() {
+        let oid = Oid::from_bytes(std::array::from_fn(|i| 3 * i as u8));
+        let hex = oid.to_hex_string();
+
+        let de = StrDeserializer::<serde::de::value::Error>::new(&hex);
+        assert_eq!(Oid::deserialize(de).unwrap(), oid);
+
+        let de = StrDeserializer::<serde::de::value::Error>::new("not an oid");
+        assert!(Oid::deserialize(de).is_err());
+    }
+}
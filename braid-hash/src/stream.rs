@@ -0,0 +1,331 @@
+//! Verified streaming of content, in the style of Bao.
+//!
+//! BLAKE3 hashes content as a binary tree of 1 KiB chunks. The outboard of a
+//! blob holds the inner nodes of that tree: an 8 byte little endian length
+//! followed by every parent node, a pair of chaining values, in pre-order.
+//!
+//! A slice is the part of the tree and the chunks needed for one byte range.
+//! An untrusted cache can [`extract_slice`] from content and its outboard, and
+//! [`decode_slice`] checks every node against the oid on the way down, so only
+//! verified bytes are written. The length in a slice is only proven by the
+//! last chunk, which slices of ranges at or past the end include.
+//!
+//! Slices are verified against the plain BLAKE3 hash of the content, see
+//! [`crate::hash`], which is only its oid under the plain BLAKE3
+//! [`HashScheme`](crate::HashScheme). Stores hashing with another scheme keep
+//! that hash, the stream root, next to the oid of the content.
+
+use std::{
+    io::{self, Read, Seek, SeekFrom, Write},
+    ops::Range,
+};
+
+use blake3::guts::{parent_cv, ChunkState};
+
+use crate::{Oid, OID_LEN};
+
+pub const CHUNK_LEN: usize = blake3::guts::CHUNK_LEN;
+
+const HEADER_LEN: usize = 8;
+const PARENT_LEN: usize = 2 * OID_LEN;
+
+/// Builds the outboard of `data`, returning it with the oid of `data`.
+pub fn outboard(data: &[u8]) -> (Oid, Vec<u8>) {
+    outboard_reader(data, data.len() as u64).expect("Reading from a slice failed")
+}
+
+/// Like [`outboard`], reading `len` bytes of content from `reader`.
+pub fn outboard_reader(mut reader: impl Read, len: u64) -> io::Result<(Oid, Vec<u8>)> {
+    let mut out = Vec::with_capacity(HEADER_LEN + parent_count(len) as usize * PARENT_LEN);
+    out.extend_from_slice(&len.to_le_bytes());
+
+    let mut buf = [0; CHUNK_LEN];
+    let root = encode(&mut reader, &mut buf, 0, len, true, &mut out)?;
+    Ok((Oid(root.into()), out))
+}
+
+fn encode(
+    reader: &mut impl Read,
+    buf: &mut [u8; CHUNK_LEN],
+    start: u64,
+    len: u64,
+    is_root: bool,
+    out: &mut Vec<u8>,
+) -> io::Result<blake3::Hash> {
+    if len <= CHUNK_LEN as u64 {
+        let chunk = &mut buf[..len as usize];
+        reader.read_exact(chunk)?;
+        return Ok(ChunkState::new(start / CHUNK_LEN as u64)
+            .update(chunk)
+            .finalize(is_root));
+    }
+
+    // the parent comes before its children, but depends on them
+    let parent = out.len();
+    out.extend_from_slice(&[0; PARENT_LEN]);
+
+    let left_len = left_len(len);
+    let left = encode(reader, buf, start, left_len, false, out)?;
+    let right = encode(reader, buf, start + left_len, len - left_len, false, out)?;
+
+    out[parent..parent + OID_LEN].copy_from_slice(left.as_bytes());
+    out[parent + OID_LEN..parent + PARENT_LEN].copy_from_slice(right.as_bytes());
+    Ok(parent_cv(&left, &right, is_root))
+}
+
+/// Extracts the slice for `len` bytes from `start` out of content and its
+/// outboard. Neither is verified, that's up to the reader of the slice.
+pub fn extract_slice(
+    outboard: &[u8],
+    mut content: impl Read + Seek,
+    start: u64,
+    len: u64,
+) -> io::Result<Vec<u8>> {
+    let (header, mut parents) = split_header(outboard)?;
+    let total = u64::from_le_bytes(header);
+    let range = chunk_range(start, len, total);
+
+    let mut out = header.to_vec();
+    extract(&mut parents, &mut content, 0, total, &range, &mut out)?;
+    Ok(out)
+}
+
+fn extract(
+    parents: &mut &[u8],
+    content: &mut (impl Read + Seek),
+    start: u64,
+    len: u64,
+    range: &Range<u64>,
+    out: &mut Vec<u8>,
+) -> io::Result<()> {
+    if len <= CHUNK_LEN as u64 {
+        let mut chunk = [0; CHUNK_LEN];
+        content.seek(SeekFrom::Start(start))?;
+        content.read_exact(&mut chunk[..len as usize])?;
+        out.extend_from_slice(&chunk[..len as usize]);
+        return Ok(());
+    }
+
+    let mut parent = [0; PARENT_LEN];
+    parents.read_exact(&mut parent)?;
+    out.extend_from_slice(&parent);
+
+    let left_len = left_len(len);
+    let halves = [(start, left_len), (start + left_len, len - left_len)];
+    for (start, len) in halves {
+        if overlaps(start, len, range) {
+            extract(parents, content, start, len, range, out)?;
+        } else {
+            let skip = parent_count(len) as usize * PARENT_LEN;
+            *parents = parents.get(skip..).ok_or_else(truncated)?;
+        }
+    }
+    Ok(())
+}
+
+/// Verifies a slice for `len` bytes from `start` against `oid` and writes
+/// those bytes to `out`. Bytes are only written once their chunk is verified.
+pub fn decode_slice(
+    oid: &Oid,
+    mut slice: impl Read,
+    start: u64,
+    len: u64,
+    mut out: impl Write,
+) -> io::Result<()> {
+    let mut header = [0; HEADER_LEN];
+    slice.read_exact(&mut header)?;
+    let total = u64::from_le_bytes(header);
+
+    let range = chunk_range(start, len, total);
+    let wanted = start..start.saturating_add(len).min(total);
+    let decoder = Decoder {
+        range: &range,
+        wanted: &wanted,
+    };
+    let root = blake3::Hash::from(oid.into_inner());
+    decoder.decode(&mut slice, 0, total, true, root, &mut out)
+}
+
+struct Decoder<'a> {
+    /// The bytes whose chunks are in the slice.
+    range: &'a Range<u64>,
+    /// The bytes to write.
+    wanted: &'a Range<u64>,
+}
+
+impl Decoder<'_> {
+    fn decode(
+        &self,
+        slice: &mut impl Read,
+        start: u64,
+        len: u64,
+        is_root: bool,
+        expected: blake3::Hash,
+        out: &mut impl Write,
+    ) -> io::Result<()> {
+        if len <= CHUNK_LEN as u64 {
+            let mut chunk = [0; CHUNK_LEN];
+            let chunk = &mut chunk[..len as usize];
+            slice.read_exact(chunk)?;
+
+            let actual = ChunkState::new(start / CHUNK_LEN as u64)
+                .update(chunk)
+                .finalize(is_root);
+            check(actual, expected)?;
+
+            let from = self.wanted.start.clamp(start, start + len) - start;
+            let to = self.wanted.end.clamp(start, start + len) - start;
+            return out.write_all(&chunk[from as usize..to as usize]);
+        }
+
+        let mut parent = [0; PARENT_LEN];
+        slice.read_exact(&mut parent)?;
+        let left = cv(&parent[..OID_LEN]);
+        let right = cv(&parent[OID_LEN..]);
+        check(parent_cv(&left, &right, is_root), expected)?;
+
+        let left_len = left_len(len);
+        let halves = [
+            (start, left_len, left),
+            (start + left_len, len - left_len, right),
+        ];
+        for (start, len, expected) in halves {
+            if overlaps(start, len, self.range) {
+                self.decode(slice, start, len, false, expected, out)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The length of the left subtree of a subtree of `len > CHUNK_LEN` bytes:
+/// the largest power of two number of chunks that leaves some bytes over.
+fn left_len(len: u64) -> u64 {
+    let full_chunks = (len - 1) / CHUNK_LEN as u64;
+    (1 << full_chunks.ilog2()) * CHUNK_LEN as u64
+}
+
+/// The number of parent nodes in a subtree of `len` bytes.
+fn parent_count(len: u64) -> u64 {
+    len.saturating_sub(1) / CHUNK_LEN as u64
+}
+
+/// The bytes whose chunks a slice includes. Empty ranges and ranges past the
+/// end still include a chunk, so the slice proves the length.
+fn chunk_range(start: u64, len: u64, total: u64) -> Range<u64> {
+    let start = start.min(total.saturating_sub(1));
+    let end = start.saturating_add(len).min(total).max(start + 1);
+    start..end
+}
+
+fn overlaps(start: u64, len: u64, range: &Range<u64>) -> bool {
+    start < range.end && range.start < start + len
+}
+
+fn split_header(outboard: &[u8]) -> io::Result<([u8; HEADER_LEN], &[u8])> {
+    let (header, parents) = outboard
+        .split_first_chunk::<HEADER_LEN>()
+        .ok_or_else(truncated)?;
+    Ok((*header, parents))
+}
+
+fn cv(bytes: &[u8]) -> blake3::Hash {
+    let bytes: [u8; OID_LEN] = bytes.try_into().expect("Chaining values are OID_LEN bytes");
+    bytes.into()
+}
+
+fn check(actual: blake3::Hash, expected: blake3::Hash) -> io::Result<()> {
+    match actual == expected {
+        true => Ok(()),
+        false => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Slice does not match its oid",
+        )),
+    }
+}
+
+fn truncated() -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "Outboard is truncated")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::{decode_slice, extract_slice, outboard, CHUNK_LEN};
+
+    fn data(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test]
+    fn test_outboard() {
+        for len in [
+            0,
+            1,
+            CHUNK_LEN,
+            CHUNK_LEN + 1,
+            4 * CHUNK_LEN,
+            5 * CHUNK_LEN + 7,
+        ] {
+            let data = data(len);
+            let (oid, outboard) = outboard(&data);
+
+            assert_eq!(oid, crate::hash(&data));
+            let parents = len.saturating_sub(1) / CHUNK_LEN;
+            assert_eq!(outboard.len(), 8 + parents * 64);
+        }
+    }
+
+    #[test]
+    fn test_slices() {
+        let data = data(5 * CHUNK_LEN + 7);
+        let (oid, outboard) = outboard(&data);
+
+        let ranges = [
+            (0, 0),
+            (0, 10),
+            (1000, 100),
+            (2 * CHUNK_LEN as u64, CHUNK_LEN as u64),
+            (5 * CHUNK_LEN as u64 + 5, 100),
+            (0, data.len() as u64),
+            (data.len() as u64 + 10, 5),
+        ];
+
+        for (start, len) in ranges {
+            let slice = extract_slice(&outboard, Cursor::new(&data), start, len).unwrap();
+            assert!(slice.len() < data.len() || len == data.len() as u64);
+
+            let mut out = Vec::new();
+            decode_slice(&oid, slice.as_slice(), start, len, &mut out).unwrap();
+            let from = (start as usize).min(data.len());
+            let to = (start + len).min(data.len() as u64) as usize;
+            assert_eq!(out, &data[from..to]);
+        }
+    }
+
+    #[test]
+    fn test_tampered() {
+        let data = data(3 * CHUNK_LEN);
+        let (oid, outboard) = outboard(&data);
+        let slice = extract_slice(&outboard, Cursor::new(&data), CHUNK_LEN as u64, 10).unwrap();
+
+        for i in [8, 8 + 64, slice.len() - 1] {
+            let mut slice = slice.clone();
+            slice[i] ^= 1;
+            let mut out = Vec::new();
+            assert!(decode_slice(&oid, slice.as_slice(), CHUNK_LEN as u64, 10, &mut out).is_err());
+        }
+
+        let mut out = Vec::new();
+        let other = crate::hash(b"other");
+        assert!(decode_slice(&other, slice.as_slice(), CHUNK_LEN as u64, 10, &mut out).is_err());
+        assert!(out.is_empty());
+
+        // only slices with the last chunk prove the length
+        let end = data.len() as u64;
+        let mut slice = extract_slice(&outboard, Cursor::new(&data), end, 0).unwrap();
+        slice[0] ^= 1;
+        assert!(decode_slice(&oid, slice.as_slice(), end, 0, &mut out).is_err());
+    }
+}